@@ -0,0 +1,56 @@
+//! The APIs of MVG the client can talk to.
+
+use serde::{Deserialize, Serialize};
+
+/// API the requests are sent to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// the legacy `/api/fahrinfo` endpoints, falling back to the bgw-pt API
+    /// when they are unavailable (server errors, 404 or unsupported
+    /// requests), and staying with whichever answered last. Timeouts and
+    /// broken responses aren't retried with the other API.
+    #[default]
+    Auto,
+    /// only the legacy `/api/fahrinfo` endpoints
+    Fahrinfo,
+    /// only the current `/api/bgw-pt/v3` endpoints
+    BgwPt,
+}
+
+impl Backend {
+    /// the backend to fall back to
+    pub(crate) fn other(self) -> Self {
+        match self {
+            Backend::BgwPt => Backend::Fahrinfo,
+            Backend::Fahrinfo | Backend::Auto => Backend::BgwPt,
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Backend::Auto),
+            "fahrinfo" => Ok(Backend::Fahrinfo),
+            "bgw-pt" => Ok(Backend::BgwPt),
+            _ => Err(format!(
+                "Unknown backend: {} (expected auto, fahrinfo or bgw-pt)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Backend::Auto => "auto",
+            Backend::Fahrinfo => "fahrinfo",
+            Backend::BgwPt => "bgw-pt",
+        };
+        f.write_str(name)
+    }
+}
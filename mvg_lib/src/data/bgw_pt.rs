@@ -0,0 +1,181 @@
+//! Responses of the bgw-pt v3 API.
+//!
+//! They are converted into the types of the legacy fahrinfo API, so callers
+//! don't have to care which backend answered. The conversions live next to
+//! the types they produce.
+
+use chrono::DateTime;
+use serde::{Deserialize, Deserializer};
+
+use super::connection::Product;
+
+/// The product of a bgw-pt transport type, `None` for ones without an
+/// equivalent like `SCHIFF` or `PEDESTRIAN`
+pub(crate) fn product(transport_type: &str) -> Option<Product> {
+    match transport_type {
+        "UBAHN" => Some(Product::UBahn),
+        "SBAHN" => Some(Product::SBahn),
+        "TRAM" => Some(Product::Tram),
+        "BUS" | "REGIONAL_BUS" => Some(Product::Bus),
+        "BAHN" => Some(Product::Bahn),
+        _ => None,
+    }
+}
+
+/// The API has no departure ids, the line and the scheduled time identify
+/// a departure well enough
+pub(crate) fn departure_id(line_id: &str, planned_millis: u64) -> String {
+    format!("{}:{}", line_id, planned_millis)
+}
+
+/// Epoch millis of the ISO 8601 times used by the routes endpoint
+fn rfc3339_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&raw)
+        .map(|t| t.timestamp_millis() as u64)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Platforms are numbers for trains and text elsewhere
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum RawPlatform {
+    Number(u32),
+    Text(String),
+}
+
+/// The platform as the legacy API has it, empty if there is none
+pub(crate) fn platform(raw: &Option<RawPlatform>) -> String {
+    match raw {
+        Some(RawPlatform::Number(n)) => n.to_string(),
+        Some(RawPlatform::Text(s)) => s.clone(),
+        None => String::new(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+pub(crate) enum Location {
+    Station(Station),
+    Address(Address),
+    Poi(Address),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Station {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub global_id: String,
+    pub diva_id: usize,
+    #[serde(default)]
+    pub place: String,
+    pub name: String,
+    #[serde(default)]
+    pub has_zoom_data: bool,
+    #[serde(default)]
+    pub transport_types: Vec<String>,
+    pub aliases: Option<String>,
+    pub surrounding_plan_link: Option<String>,
+    #[serde(default)]
+    pub tariff_zones: String,
+}
+
+/// Addresses and points of interest
+#[derive(Deserialize, Debug)]
+pub(crate) struct Address {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub place: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Departure {
+    pub planned_departure_time: u64,
+    pub realtime: bool,
+    pub delay_in_minutes: Option<i32>,
+    pub transport_type: String,
+    pub label: String,
+    #[serde(default)]
+    pub diva_id: String,
+    pub destination: String,
+    #[serde(default)]
+    pub cancelled: bool,
+    #[serde(default)]
+    pub sev: bool,
+    pub platform: Option<RawPlatform>,
+    pub stop_position_number: Option<u8>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Connection {
+    pub parts: Vec<ConnectionPart>,
+    #[serde(default)]
+    pub ticketing_information: TicketingInformation,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TicketingInformation {
+    #[serde(default)]
+    pub zones: Vec<u8>,
+    #[serde(default)]
+    pub unified_ticket_ids: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConnectionPart {
+    pub from: Stop,
+    pub to: Stop,
+    #[serde(default)]
+    pub intermediate_stops: Vec<Stop>,
+    pub line: Line,
+    #[serde(default)]
+    pub no_changing_required: bool,
+}
+
+/// Start, end or intermediate stop of a connection part
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Stop {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// missing for addresses
+    pub station_global_id: Option<String>,
+    pub station_diva_id: Option<usize>,
+    #[serde(default)]
+    pub place: String,
+    pub name: String,
+    /// the time at this stop, also for arrivals
+    #[serde(deserialize_with = "rfc3339_millis")]
+    pub planned_departure: u64,
+    pub departure_delay_in_minutes: Option<i32>,
+    pub arrival_delay_in_minutes: Option<i32>,
+    pub platform: Option<RawPlatform>,
+    pub stop_position_number: Option<u8>,
+    #[serde(default)]
+    pub transport_types: Vec<String>,
+    #[serde(default)]
+    pub has_zoom_data: bool,
+    #[serde(default)]
+    pub is_cancelled: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Line {
+    #[serde(default)]
+    pub label: String,
+    pub transport_type: String,
+    #[serde(default)]
+    pub destination: String,
+    #[serde(default)]
+    pub sev: bool,
+    #[serde(default)]
+    pub diva_id: String,
+}
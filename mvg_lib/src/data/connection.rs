@@ -1,7 +1,11 @@
 use serde::{Serialize, Deserialize};
+use super::bgw_pt;
 use super::location::Location;
+use super::platform::Platform;
+use super::from_millis;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration};
+use chrono_tz::Tz;
 
 /// returned by the mvg api
 #[derive(Serialize, Deserialize, Debug)]
@@ -79,7 +83,7 @@ pub struct Footway{
     to: Location,
     path: Vec<Location>,
     path_description: Vec<PathDescriptor>,
-    interchange_path: Vec<()>,
+    interchange_path: Vec<Location>,
     departure: u64,
     arrival: u64,
     cancelled: bool,
@@ -91,6 +95,7 @@ pub struct Footway{
 }
 
 /// Representing one part of a connection.
+#[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "connectionPartType")]
 #[serde(rename_all = "UPPERCASE")]
@@ -99,21 +104,73 @@ pub enum ConnectionPart{
     Footway(Footway)
 }
 
+/// Floor level of a section of a path
 #[derive(Serialize, Deserialize, Debug)]
-struct PathDescriptor{
+pub struct PathDescriptor{
     from: u8,
     to: u8,
     level: i8
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl PathDescriptor{
+    /// index of the first path location of the section
+    pub fn from(&self) -> u8{
+        self.from
+    }
+
+    /// index of the last path location of the section
+    pub fn to(&self) -> u8{
+        self.to
+    }
+
+    /// floor level, 0 is the street level
+    pub fn level(&self) -> i8{
+        self.level
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Product{
     SBahn,
     UBahn,
     Bus,
     Bahn,
-    Tram
+    Tram,
+    /// a product the library doesn't know, e.g. a ferry
+    #[serde(other)]
+    Other
+}
+
+impl std::fmt::Display for Product {
+    /// API name of the product (e.g. `UBAHN`)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Product::SBahn => "SBAHN",
+            Product::UBahn => "UBAHN",
+            Product::Bus => "BUS",
+            Product::Bahn => "BAHN",
+            Product::Tram => "TRAM",
+            Product::Other => "OTHER",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Product {
+    type Err = String;
+
+    /// Parse a product from its API name (e.g. `UBAHN`) or short letter (e.g. `U`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SBAHN" | "S" => Ok(Product::SBahn),
+            "UBAHN" | "U" => Ok(Product::UBahn),
+            "BUS" | "B" => Ok(Product::Bus),
+            "BAHN" => Ok(Product::Bahn),
+            "TRAM" | "T" => Ok(Product::Tram),
+            _ => Err(format!("Unknown product: {}", s)),
+        }
+    }
 }
 
 impl Connection{
@@ -128,25 +185,212 @@ impl Connection{
     }
 
     /// start time
-    pub fn departure_time(&self) -> DateTime<Local> {
-        let time =
-            std::time::UNIX_EPOCH + std::time::Duration::from_millis(self.departure as u64);
-        let time = DateTime::<Local>::from(time);
-        time
+    pub fn departure_time(&self) -> DateTime<Tz> {
+        from_millis(self.departure)
     }
 
     /// end time
-    pub fn arrival_time(&self) -> DateTime<Local> {
-        let time =
-            std::time::UNIX_EPOCH + std::time::Duration::from_millis(self.arrival as u64);
-        let time = DateTime::<Local>::from(time);
-        time
+    pub fn arrival_time(&self) -> DateTime<Tz> {
+        from_millis(self.arrival)
     }
 
     /// list of different connection parts
     pub fn connection_parts(&self) -> &Vec<ConnectionPart>{
         &self.connection_part_list
     }
+
+    /// total travel time
+    pub fn duration(&self) -> Duration {
+        self.arrival_time() - self.departure_time()
+    }
+
+    /// number of changes between products, staying seated when a line
+    /// continues under another label isn't a change
+    pub fn changes(&self) -> usize {
+        self.transportations()
+            .skip(1)
+            .filter(|tp| !tp.no_changing_required())
+            .count()
+    }
+
+    /// the parts of the connection which use public transport
+    pub fn transportations(&self) -> impl Iterator<Item = &Transportation> {
+        self.connection_part_list.iter().filter_map(|p| match p {
+            ConnectionPart::Transportation(tp) => Some(tp),
+            ConnectionPart::Footway(_) => None,
+        })
+    }
+
+    /// the parts of the connection which have to be walked
+    pub fn footways(&self) -> impl Iterator<Item = &Footway> {
+        self.connection_part_list.iter().filter_map(|p| match p {
+            ConnectionPart::Footway(fw) => Some(fw),
+            ConnectionPart::Transportation(_) => None,
+        })
+    }
+
+    /// total time spent walking
+    pub fn walking_time(&self) -> Duration {
+        self.footways()
+            .fold(Duration::zero(), |sum, fw| sum + fw.duration())
+    }
+
+    /// whether any part of the connection is cancelled
+    pub fn cancelled(&self) -> bool {
+        self.connection_part_list.iter().any(|p| p.cancelled())
+    }
+
+    /// EFA ids of the tickets valid for this connection
+    pub fn efa_ticket_ids(&self) -> &Vec<String> {
+        &self.efa_ticket_ids
+    }
+
+    /// first and last tariff ring passed
+    pub fn rings(&self) -> (u8, u8) {
+        (self.ring_from, self.ring_to)
+    }
+}
+
+impl Connection {
+    /// `None` for connections without parts
+    pub(crate) fn from_bgw_pt(raw: bgw_pt::Connection) -> Option<Self> {
+        let first = raw.parts.first()?;
+        let last = raw.parts.last()?;
+        let (from, departure) = (Location::from(&first.from), first.from.planned_departure);
+        let (to, arrival) = (Location::from(&last.to), last.to.planned_departure);
+        let connection_part_list = raw
+            .parts
+            .into_iter()
+            .map(ConnectionPart::from_bgw_pt)
+            .collect();
+        let zones = raw.ticketing_information.zones;
+        Some(Connection {
+            zoom_notice_to: false,
+            zoom_notice_from: false,
+            from,
+            to,
+            departure,
+            arrival,
+            connection_part_list,
+            efa_ticket_ids: raw.ticketing_information.unified_ticket_ids,
+            server_id: 0,
+            ring_from: zones.iter().copied().min().unwrap_or(0),
+            ring_to: zones.iter().copied().max().unwrap_or(0),
+            old_tarif: false,
+            banner_hash: String::new(),
+        })
+    }
+}
+
+impl ConnectionPart {
+    /// Walks are parts with the transport type `PEDESTRIAN`, unknown transport
+    /// types become [`Product::Other`]. The API sends an encoded polyline
+    /// instead of path coordinates, paths stay empty.
+    fn from_bgw_pt(raw: bgw_pt::ConnectionPart) -> Self {
+        let departure = raw.from.planned_departure;
+        let arrival = raw.to.planned_departure;
+        if raw.line.transport_type == "PEDESTRIAN" {
+            return ConnectionPart::Footway(Footway {
+                from: Location::from(&raw.from),
+                to: Location::from(&raw.to),
+                path: Vec::new(),
+                path_description: Vec::new(),
+                interchange_path: Vec::new(),
+                departure,
+                arrival,
+                cancelled: false,
+                zoom_notice_departure: raw.from.has_zoom_data,
+                zoom_notice_arrival: raw.to.has_zoom_data,
+                departure_stop_position_number: raw.from.stop_position_number.unwrap_or(0),
+                arrival_stop_position_number: raw.to.stop_position_number.unwrap_or(0),
+                no_changing_required: raw.no_changing_required,
+            });
+        }
+        let stops = raw
+            .intermediate_stops
+            .iter()
+            .map(|stop| Stop {
+                location: Location::from(stop),
+                time: stop.planned_departure,
+                delay: stop.departure_delay_in_minutes.unwrap_or(0),
+                arr_delay: stop.arrival_delay_in_minutes.unwrap_or(0),
+            })
+            .collect();
+        ConnectionPart::Transportation(Transportation {
+            stops,
+            from: Location::from(&raw.from),
+            to: Location::from(&raw.to),
+            path: Vec::new(),
+            path_description: Vec::new(),
+            interchange_path: Vec::new(),
+            departure,
+            arrival,
+            delay: raw.from.departure_delay_in_minutes.unwrap_or(0),
+            arr_delay: raw.to.arrival_delay_in_minutes.unwrap_or(0),
+            cancelled: raw.from.is_cancelled || raw.to.is_cancelled,
+            product: bgw_pt::product(&raw.line.transport_type).unwrap_or(Product::Other),
+            label: raw.line.label,
+            server_id: String::new(),
+            destination: raw.line.destination,
+            sev: raw.line.sev,
+            zoom_notice_departure: raw.from.has_zoom_data,
+            zoom_notice_arrival: raw.to.has_zoom_data,
+            departure_platform: bgw_pt::platform(&raw.from.platform),
+            departure_stop_position_number: raw.from.stop_position_number.unwrap_or(0),
+            arrival_platform: bgw_pt::platform(&raw.to.platform),
+            arrival_stop_position_number: raw.to.stop_position_number.unwrap_or(0),
+            no_changing_required: raw.no_changing_required,
+            from_id: raw.from.station_global_id.clone().unwrap_or_default(),
+            departure_id: bgw_pt::departure_id(&raw.line.diva_id, departure),
+            info_messages: None,
+        })
+    }
+}
+
+impl ConnectionPart{
+    /// starting location
+    pub fn from(&self) -> &Location{
+        match self {
+            ConnectionPart::Transportation(tp) => tp.from(),
+            ConnectionPart::Footway(fw) => fw.from(),
+        }
+    }
+
+    /// destination
+    pub fn to(&self) -> &Location{
+        match self {
+            ConnectionPart::Transportation(tp) => tp.to(),
+            ConnectionPart::Footway(fw) => fw.to(),
+        }
+    }
+
+    /// scheduled start
+    pub fn departure_time(&self) -> DateTime<Tz> {
+        match self {
+            ConnectionPart::Transportation(tp) => tp.departure_time(),
+            ConnectionPart::Footway(fw) => fw.departure_time(),
+        }
+    }
+
+    /// scheduled end
+    pub fn arrival_time(&self) -> DateTime<Tz> {
+        match self {
+            ConnectionPart::Transportation(tp) => tp.arrival_time(),
+            ConnectionPart::Footway(fw) => fw.arrival_time(),
+        }
+    }
+
+    /// scheduled duration
+    pub fn duration(&self) -> Duration {
+        self.arrival_time() - self.departure_time()
+    }
+
+    pub fn cancelled(&self) -> bool {
+        match self {
+            ConnectionPart::Transportation(tp) => tp.cancelled(),
+            ConnectionPart::Footway(fw) => fw.cancelled(),
+        }
+    }
 }
 
 impl Transportation{
@@ -169,4 +413,186 @@ impl Transportation{
     pub fn label(&self) -> &String{
         &self.label
     }
-}
\ No newline at end of file
+
+    /// final destination of the line
+    pub fn destination(&self) -> &String{
+        &self.destination
+    }
+
+    /// scheduled departure at the starting location
+    pub fn departure_time(&self) -> DateTime<Tz> {
+        from_millis(self.departure)
+    }
+
+    /// scheduled arrival at the destination
+    pub fn arrival_time(&self) -> DateTime<Tz> {
+        from_millis(self.arrival)
+    }
+
+    /// delay at the starting location
+    pub fn delay(&self) -> Duration {
+        Duration::minutes(self.delay as i64)
+    }
+
+    /// delay at the destination
+    pub fn arrival_delay(&self) -> Duration {
+        Duration::minutes(self.arr_delay as i64)
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// whether this is a replacement service (Schienenersatzverkehr)
+    pub fn sev(&self) -> bool {
+        self.sev
+    }
+
+    /// expected departure including the delay
+    pub fn realtime_departure_time(&self) -> DateTime<Tz> {
+        self.departure_time() + self.delay()
+    }
+
+    /// expected arrival including the delay
+    pub fn realtime_arrival_time(&self) -> DateTime<Tz> {
+        self.arrival_time() + self.arrival_delay()
+    }
+
+    /// scheduled duration
+    pub fn duration(&self) -> Duration {
+        self.arrival_time() - self.departure_time()
+    }
+
+    /// platform at the starting location, if the API delivers one
+    pub fn departure_platform(&self) -> Option<Platform> {
+        Platform::from_api(&self.departure_platform)
+    }
+
+    /// platform at the destination, if the API delivers one
+    pub fn arrival_platform(&self) -> Option<Platform> {
+        Platform::from_api(&self.arrival_platform)
+    }
+
+    pub fn departure_stop_position_number(&self) -> u8 {
+        self.departure_stop_position_number
+    }
+
+    pub fn arrival_stop_position_number(&self) -> u8 {
+        self.arrival_stop_position_number
+    }
+
+    /// notes about this part of the connection
+    pub fn info_messages(&self) -> &[String] {
+        self.info_messages.as_deref().unwrap_or(&[])
+    }
+
+    /// intermediate stops between start and destination
+    pub fn stops(&self) -> &Vec<Stop> {
+        &self.stops
+    }
+
+    /// geographic path of the ride
+    pub fn path(&self) -> &Vec<Location> {
+        &self.path
+    }
+
+    /// floor levels along the path
+    pub fn path_description(&self) -> &Vec<PathDescriptor> {
+        &self.path_description
+    }
+
+    /// path to walk when changing to this transportation
+    pub fn interchange_path(&self) -> &Vec<Location> {
+        &self.interchange_path
+    }
+
+    /// whether passengers can stay seated when the line changes its label
+    pub fn no_changing_required(&self) -> bool {
+        self.no_changing_required
+    }
+
+    /// id of the starting station
+    pub fn from_id(&self) -> &String {
+        &self.from_id
+    }
+
+    /// id of the departure, as in [`Departure::departure_id`](super::departure::Departure::departure_id)
+    pub fn departure_id(&self) -> &String {
+        &self.departure_id
+    }
+}
+
+impl Stop{
+    pub fn location(&self) -> &Location{
+        &self.location
+    }
+
+    /// scheduled time at this stop
+    pub fn time(&self) -> DateTime<Tz> {
+        from_millis(self.time)
+    }
+
+    /// delay of the departure at this stop
+    pub fn delay(&self) -> Duration {
+        Duration::minutes(self.delay as i64)
+    }
+
+    /// delay of the arrival at this stop
+    pub fn arrival_delay(&self) -> Duration {
+        Duration::minutes(self.arr_delay as i64)
+    }
+
+    /// expected time at this stop including the delay
+    pub fn realtime_time(&self) -> DateTime<Tz> {
+        self.time() + self.delay()
+    }
+}
+
+impl Footway{
+    /// starting location
+    pub fn from(&self) -> &Location{
+        &self.from
+    }
+
+    /// destination
+    pub fn to(&self) -> &Location{
+        &self.to
+    }
+
+    /// start of the walk
+    pub fn departure_time(&self) -> DateTime<Tz> {
+        from_millis(self.departure)
+    }
+
+    /// end of the walk
+    pub fn arrival_time(&self) -> DateTime<Tz> {
+        from_millis(self.arrival)
+    }
+
+    /// time needed for the walk
+    pub fn duration(&self) -> Duration {
+        self.arrival_time() - self.departure_time()
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// geographic path of the walk
+    pub fn path(&self) -> &Vec<Location> {
+        &self.path
+    }
+
+    /// floor levels along the path
+    pub fn path_description(&self) -> &Vec<PathDescriptor> {
+        &self.path_description
+    }
+
+    pub fn departure_stop_position_number(&self) -> u8 {
+        self.departure_stop_position_number
+    }
+
+    pub fn arrival_stop_position_number(&self) -> u8 {
+        self.arrival_stop_position_number
+    }
+}
@@ -1,7 +1,13 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
+use super::bgw_pt;
 use super::connection::Product;
+use super::line::LineGroup;
+use super::platform::Platform;
+use super::station_id::StationId;
+use super::{from_millis, MVGError};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DepartureInfo {
@@ -10,7 +16,8 @@ pub struct DepartureInfo {
     pub departures: Vec<Departure>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A line departing at a station, together with one of its destinations
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ServingLine {
     destination: String,
     sev: bool,
@@ -23,7 +30,63 @@ pub struct ServingLine {
     diva_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ServingLine {
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    /// whether this is a replacement service (Schienenersatzverkehr)
+    pub fn sev(&self) -> bool {
+        self.sev
+    }
+
+    /// the part of the network operating the line, e.g. `swm`
+    pub fn partial_net(&self) -> &str {
+        &self.partial_net
+    }
+
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// line label, e.g. `U6`
+    pub fn line_number(&self) -> &str {
+        &self.line_number
+    }
+
+    /// id of the line in the DIVA system, e.g. `010U6`
+    pub fn diva_id(&self) -> &str {
+        &self.diva_id
+    }
+
+    pub fn group(&self) -> LineGroup {
+        LineGroup::of(self.product, &self.line_number)
+    }
+}
+
+/// The lines of `departures`, once per line and destination, for APIs
+/// which don't list them separately
+pub(crate) fn serving_lines(departures: &[Departure]) -> Vec<ServingLine> {
+    let mut lines: Vec<ServingLine> = Vec::new();
+    for dep in departures {
+        let known = lines
+            .iter()
+            .any(|l| l.line_number == dep.label && l.destination == dep.destination);
+        if !known {
+            lines.push(ServingLine {
+                destination: dep.destination.clone(),
+                sev: dep.sev,
+                partial_net: String::new(),
+                product: dep.product,
+                line_number: dep.label.clone(),
+                diva_id: String::new(),
+            });
+        }
+    }
+    lines
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Departure {
     #[serde(rename = "departureTime")]
     departure_time: u128,
@@ -40,14 +103,56 @@ pub struct Departure {
     platform: String,
     #[serde(rename = "stopPositionNumber")]
     stop_position_number: u8,
+    /// delay in minutes, only present for live data
+    #[serde(default)]
+    delay: Option<i32>,
 }
 
 impl Departure {
-    pub fn departure_time(&self) -> DateTime<Local> {
-        let time =
-            std::time::UNIX_EPOCH + std::time::Duration::from_millis(self.departure_time as u64);
-        let time = DateTime::<Local>::from(time);
-        time
+    /// scheduled departure time
+    pub fn departure_time(&self) -> DateTime<Tz> {
+        from_millis(self.departure_time as u64)
+    }
+
+    /// expected departure time including the delay, if known
+    pub fn realtime_departure_time(&self) -> DateTime<Tz> {
+        self.departure_time() + self.delay().unwrap_or_else(Duration::zero)
+    }
+
+    /// delay of the departure, only known for live data
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay.map(|d| Duration::minutes(d as i64))
+    }
+
+    /// whether realtime data is available for this departure
+    pub fn live(&self) -> bool {
+        self.live
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// whether this is a replacement service (Schienenersatzverkehr)
+    pub fn sev(&self) -> bool {
+        self.sev
+    }
+
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// platform or stop position, if the API delivers one
+    pub fn platform(&self) -> Option<Platform> {
+        Platform::from_api(&self.platform)
+    }
+
+    pub fn departure_id(&self) -> String {
+        self.departure_id.clone()
+    }
+
+    pub fn stop_position_number(&self) -> u8 {
+        self.stop_position_number
     }
 
     pub fn label(&self) -> String{
@@ -61,4 +166,130 @@ impl Departure {
     pub fn line_background_color(&self) -> String{
         self.line_background_color.clone()
     }
-}
\ No newline at end of file
+
+    /// Unknown transport types become [`Product::Other`]
+    pub(crate) fn from_bgw_pt(raw: bgw_pt::Departure) -> Self {
+        Departure {
+            departure_time: raw.planned_departure_time as u128,
+            product: bgw_pt::product(&raw.transport_type).unwrap_or(Product::Other),
+            departure_id: bgw_pt::departure_id(&raw.diva_id, raw.planned_departure_time),
+            label: raw.label,
+            destination: raw.destination,
+            live: raw.realtime,
+            cancelled: raw.cancelled,
+            // the API leaves the colors to the client
+            line_background_color: String::new(),
+            sev: raw.sev,
+            platform: bgw_pt::platform(&raw.platform),
+            stop_position_number: raw.stop_position_number.unwrap_or(0),
+            delay: if raw.realtime { raw.delay_in_minutes } else { None },
+        }
+    }
+}
+
+/// Selects departures by product, line, destination and platform.
+///
+/// Values given for the same criterion are alternatives, different
+/// criteria all have to match. An empty filter matches every departure.
+#[derive(Debug, Clone, Default)]
+pub struct DepartureFilter {
+    products: Vec<Product>,
+    lines: Vec<String>,
+    destination: Option<String>,
+    platforms: Vec<String>,
+}
+
+impl DepartureFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match departures of this product
+    pub fn product(mut self, product: Product) -> Self {
+        self.products.push(product);
+        self
+    }
+
+    /// Match departures of the line with this label (e.g. U3)
+    pub fn line(mut self, label: &str) -> Self {
+        self.lines.push(label.to_string());
+        self
+    }
+
+    /// Match departures whose destination contains `destination`
+    pub fn destination(mut self, destination: &str) -> Self {
+        self.destination = Some(destination.to_lowercase());
+        self
+    }
+
+    /// Match departures from this platform
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.platforms.push(platform.to_string());
+        self
+    }
+
+    pub fn matches(&self, departure: &Departure) -> bool {
+        (self.products.is_empty() || self.products.contains(&departure.product))
+            && (self.lines.is_empty()
+                || self
+                    .lines
+                    .iter()
+                    .any(|l| l.eq_ignore_ascii_case(&departure.label)))
+            && self
+                .destination
+                .as_ref()
+                .is_none_or(|d| departure.destination.to_lowercase().contains(d))
+            && (self.platforms.is_empty()
+                || departure
+                    .platform()
+                    .is_some_and(|platform| self.platforms.iter().any(|p| platform.matches(p))))
+    }
+
+    /// Keep only the matching departures
+    pub fn apply(&self, departures: Vec<Departure>) -> Vec<Departure> {
+        departures.into_iter().filter(|d| self.matches(d)).collect()
+    }
+}
+
+/// Departures of several stations, see [`crate::MVG::departures_multi`]
+#[derive(Debug, Default)]
+pub struct MultiDepartures {
+    departures: Vec<(StationId, Departure)>,
+    errors: Vec<(StationId, MVGError)>,
+}
+
+impl MultiDepartures {
+    /// Merge the results of single stations, keyed by station id
+    pub fn new(results: Vec<(StationId, Result<Vec<Departure>, MVGError>)>) -> Self {
+        let mut merged = MultiDepartures::default();
+        for (station_id, result) in results {
+            match result {
+                Ok(departures) => merged
+                    .departures
+                    .extend(departures.into_iter().map(|d| (station_id.clone(), d))),
+                Err(e) => merged.errors.push((station_id, e)),
+            }
+        }
+        // stable sort keeps the order of the stations for equal times
+        merged
+            .departures
+            .sort_by_key(|(_, d)| d.realtime_departure_time());
+        merged
+    }
+
+    /// Departures of all stations with the id of their station, sorted by
+    /// realtime departure time
+    pub fn departures(&self) -> &[(StationId, Departure)] {
+        &self.departures
+    }
+
+    /// Stations whose departures couldn't be fetched
+    pub fn errors(&self) -> &[(StationId, MVGError)] {
+        &self.errors
+    }
+
+    /// whether the departures of all stations could be fetched
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use super::connection::Product;
+use super::from_millis;
+
+/// returned by the mvg api
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InterruptionList {
+    #[serde(default)]
+    pub interruption: Vec<Interruption>,
+}
+
+/// A service interruption or planned change of service
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Interruption {
+    id: String,
+    title: String,
+    #[serde(default)]
+    text: String,
+    duration: Validity,
+    #[serde(default)]
+    affected_lines: AffectedLines,
+    modification_date: Option<u64>,
+}
+
+/// Time span during which an interruption is in effect
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Validity {
+    #[serde(default)]
+    text: String,
+    from: Option<u64>,
+    until: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AffectedLines {
+    #[serde(default)]
+    line: Vec<AffectedLine>,
+}
+
+/// A line affected by an interruption
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AffectedLine {
+    line: String,
+    product: String,
+}
+
+impl Interruption {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// description as delivered by the API, may contain HTML markup
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// description with HTML markup removed
+    pub fn plain_text(&self) -> String {
+        let text = self
+            .text
+            .replace("<br>", "\n")
+            .replace("<br/>", "\n")
+            .replace("<br />", "\n");
+        let mut plain = String::new();
+        let mut in_tag = false;
+        for c in text.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                c if !in_tag => plain.push(c),
+                _ => {}
+            }
+        }
+        plain
+            .replace("&nbsp;", " ")
+            .replace("&amp;", "&")
+            .trim()
+            .to_string()
+    }
+
+    /// human readable description of the validity window
+    pub fn validity_text(&self) -> &str {
+        &self.duration.text
+    }
+
+    /// start of the validity window
+    pub fn valid_from(&self) -> Option<DateTime<Tz>> {
+        self.duration.from.map(from_millis)
+    }
+
+    /// end of the validity window, if known
+    pub fn valid_until(&self) -> Option<DateTime<Tz>> {
+        self.duration.until.map(from_millis)
+    }
+
+    /// time of the last update of this interruption
+    pub fn modification_date(&self) -> Option<DateTime<Tz>> {
+        self.modification_date.map(from_millis)
+    }
+
+    pub fn affected_lines(&self) -> &Vec<AffectedLine> {
+        &self.affected_lines.line
+    }
+
+    /// whether the line with the given label (e.g. U3) is affected
+    pub fn affects_line(&self, label: &str) -> bool {
+        self.affected_lines()
+            .iter()
+            .any(|l| l.label().eq_ignore_ascii_case(label))
+    }
+
+    /// whether any line of the given product is affected
+    pub fn affects_product(&self, product: Product) -> bool {
+        self.affected_lines()
+            .iter()
+            .any(|l| l.product() == Some(product))
+    }
+}
+
+impl AffectedLine {
+    /// label of the line (e.g. U3)
+    pub fn label(&self) -> &str {
+        &self.line
+    }
+
+    /// product of the line, if the API value is known
+    pub fn product(&self) -> Option<Product> {
+        self.product.parse().ok()
+    }
+}
@@ -0,0 +1,196 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use super::connection::Product;
+
+/// The groups the API sorts the lines of a station into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LineGroup {
+    UBahn,
+    SBahn,
+    Tram,
+    NightTram,
+    Bus,
+    NightBus,
+    Other,
+}
+
+impl LineGroup {
+    /// every group, in the order they are listed
+    pub const ALL: [LineGroup; 7] = [
+        LineGroup::UBahn,
+        LineGroup::SBahn,
+        LineGroup::Tram,
+        LineGroup::NightTram,
+        LineGroup::Bus,
+        LineGroup::NightBus,
+        LineGroup::Other,
+    ];
+
+    /// The group of a line, night lines are the ones whose label starts
+    /// with `N` (e.g. `N40`)
+    pub fn of(product: Product, label: &str) -> Self {
+        let night = label.starts_with('N');
+        match product {
+            Product::UBahn => LineGroup::UBahn,
+            Product::SBahn => LineGroup::SBahn,
+            Product::Tram if night => LineGroup::NightTram,
+            Product::Tram => LineGroup::Tram,
+            Product::Bus if night => LineGroup::NightBus,
+            Product::Bus => LineGroup::Bus,
+            Product::Bahn | Product::Other => LineGroup::Other,
+        }
+    }
+
+    /// `None` for [`LineGroup::Other`]
+    pub fn product(self) -> Option<Product> {
+        match self {
+            LineGroup::UBahn => Some(Product::UBahn),
+            LineGroup::SBahn => Some(Product::SBahn),
+            LineGroup::Tram | LineGroup::NightTram => Some(Product::Tram),
+            LineGroup::Bus | LineGroup::NightBus => Some(Product::Bus),
+            LineGroup::Other => None,
+        }
+    }
+
+    /// name of the group in the API, e.g. `nachtbus`
+    pub fn key(self) -> &'static str {
+        match self {
+            LineGroup::UBahn => "ubahn",
+            LineGroup::SBahn => "sbahn",
+            LineGroup::Tram => "tram",
+            LineGroup::NightTram => "nachttram",
+            LineGroup::Bus => "bus",
+            LineGroup::NightBus => "nachtbus",
+            LineGroup::Other => "otherlines",
+        }
+    }
+}
+
+impl std::fmt::Display for LineGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LineGroup::UBahn => "U-Bahn",
+            LineGroup::SBahn => "S-Bahn",
+            LineGroup::Tram => "Tram",
+            LineGroup::NightTram => "Night tram",
+            LineGroup::Bus => "Bus",
+            LineGroup::NightBus => "Night bus",
+            LineGroup::Other => "Other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Labels of the lines serving a station, grouped like the API does
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Lines {
+    ubahn: Vec<String>,
+    sbahn: Vec<String>,
+    tram: Vec<String>,
+    nachttram: Vec<String>,
+    bus: Vec<String>,
+    nachtbus: Vec<String>,
+    otherlines: Vec<String>,
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ubahn(&self) -> &[String] {
+        &self.ubahn
+    }
+
+    pub fn sbahn(&self) -> &[String] {
+        &self.sbahn
+    }
+
+    pub fn tram(&self) -> &[String] {
+        &self.tram
+    }
+
+    pub fn night_tram(&self) -> &[String] {
+        &self.nachttram
+    }
+
+    pub fn bus(&self) -> &[String] {
+        &self.bus
+    }
+
+    pub fn night_bus(&self) -> &[String] {
+        &self.nachtbus
+    }
+
+    /// lines of other products, e.g. regional trains
+    pub fn other(&self) -> &[String] {
+        &self.otherlines
+    }
+
+    /// labels of the lines in `group`
+    pub fn group(&self, group: LineGroup) -> &[String] {
+        match group {
+            LineGroup::UBahn => &self.ubahn,
+            LineGroup::SBahn => &self.sbahn,
+            LineGroup::Tram => &self.tram,
+            LineGroup::NightTram => &self.nachttram,
+            LineGroup::Bus => &self.bus,
+            LineGroup::NightBus => &self.nachtbus,
+            LineGroup::Other => &self.otherlines,
+        }
+    }
+
+    /// the groups with at least one line, in the order of [`LineGroup::ALL`]
+    pub fn groups(&self) -> Vec<(LineGroup, &[String])> {
+        LineGroup::ALL
+            .iter()
+            .map(|&group| (group, self.group(group)))
+            .filter(|(_, labels)| !labels.is_empty())
+            .collect()
+    }
+
+    /// number of lines in all groups
+    pub fn len(&self) -> usize {
+        LineGroup::ALL.iter().map(|&group| self.group(group).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a line to `group` unless it's already listed. The group is kept
+    /// sorted by line number, so `U3` comes before `U6` and `52` before `132`.
+    pub fn add(&mut self, group: LineGroup, label: &str) {
+        let labels = match group {
+            LineGroup::UBahn => &mut self.ubahn,
+            LineGroup::SBahn => &mut self.sbahn,
+            LineGroup::Tram => &mut self.tram,
+            LineGroup::NightTram => &mut self.nachttram,
+            LineGroup::Bus => &mut self.bus,
+            LineGroup::NightBus => &mut self.nachtbus,
+            LineGroup::Other => &mut self.otherlines,
+        };
+        if !labels.iter().any(|l| l == label) {
+            labels.push(label.to_string());
+            labels.sort_by(|a, b| compare_labels(a, b));
+        }
+    }
+}
+
+/// Orders labels by their letters first and their number second
+fn compare_labels(a: &str, b: &str) -> Ordering {
+    let split = |label: &str| {
+        let letters: String = label.chars().take_while(|c| !c.is_ascii_digit()).collect();
+        let number: Option<u32> = label[letters.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .ok();
+        (letters, number)
+    };
+    split(a).cmp(&split(b)).then_with(|| a.cmp(b))
+}
@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
+use super::bgw_pt;
 use super::connection::Product;
+use super::line::Lines;
+use super::station_id::StationId;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Station {
     latitude: f64,
     longitude: f64,
-    id: String,
+    id: StationId,
     #[serde(rename = "divaId")]
     diva_id: usize,
     place: String,
@@ -19,7 +22,9 @@ pub struct Station {
     link: Option<String>,
     #[serde(rename = "tariffZones")]
     tariff_zones: String,
-    lines: serde_json::Value,
+    /// boxed, it's large and usually empty
+    #[serde(default)]
+    lines: Box<Lines>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -53,9 +58,87 @@ pub struct Locations {
     pub locations: Vec<Location>,
 }
 
+impl Address{
+    pub fn street(&self) -> String{
+        self.street.clone()
+    }
+
+    pub fn place(&self) -> String{
+        self.place.clone()
+    }
+}
+
+/// A location found near a point, together with its distance to that point
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NearbyLocation {
+    location: Location,
+    distance: f64,
+}
+
+/// Mean earth radius in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Great-circle distance in meters between two coordinates
+pub fn distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+impl Location {
+    /// latitude and longitude of the location
+    pub fn coordinates(&self) -> (f64, f64) {
+        match self {
+            Location::Station(s) => (s.latitude, s.longitude),
+            Location::Address(a) => (a.latitude, a.longitude),
+            Location::Location(p) => (p.latitude as f64, p.longitude as f64),
+        }
+    }
+
+    /// distance in meters to the given coordinates
+    pub fn distance_to(&self, latitude: f64, longitude: f64) -> f64 {
+        let (lat, lon) = self.coordinates();
+        distance(lat, lon, latitude, longitude)
+    }
+}
+
+impl NearbyLocation {
+    pub fn new(location: Location, latitude: f64, longitude: f64) -> Self {
+        let distance = location.distance_to(latitude, longitude);
+        NearbyLocation { location, distance }
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// distance in meters to the queried point
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+}
+
 impl Station{
-    pub fn id(&self) -> String{
-        self.id.clone()
+    pub fn id(&self) -> &StationId{
+        &self.id
+    }
+
+    /// number of the station in the DIVA system
+    pub fn diva_id(&self) -> usize{
+        self.diva_id
+    }
+
+    /// whether `id` denotes this station, global ids have to match
+    /// exactly, numeric ones the DIVA id
+    pub fn has_id(&self, id: &StationId) -> bool{
+        if id.is_global() {
+            self.id == *id
+        } else {
+            // stations of the bgw-pt API may come without DIVA id
+            self.diva_id != 0 && id.diva_id() == Some(self.diva_id as u32)
+        }
     }
 
     pub fn name(&self) -> String{
@@ -65,4 +148,97 @@ impl Station{
     pub fn place(&self) -> String{
         self.place.clone()
     }
-}
\ No newline at end of file
+
+    pub fn latitude(&self) -> f64{
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64{
+        self.longitude
+    }
+
+    /// products serving this station
+    pub fn products(&self) -> &Vec<Product>{
+        &self.products
+    }
+
+    /// lines serving this station, empty if the API didn't list them
+    pub fn lines(&self) -> &Lines{
+        &self.lines
+    }
+}
+
+impl From<bgw_pt::Station> for Station {
+    fn from(raw: bgw_pt::Station) -> Self {
+        let mut products: Vec<Product> = Vec::new();
+        for product in raw.transport_types.iter().filter_map(|t| bgw_pt::product(t)) {
+            if !products.contains(&product) {
+                products.push(product);
+            }
+        }
+        Station {
+            latitude: raw.latitude,
+            longitude: raw.longitude,
+            id: StationId::from_api(raw.global_id),
+            diva_id: raw.diva_id,
+            place: raw.place,
+            name: raw.name,
+            has_live_data: true,
+            has_zoom_data: raw.has_zoom_data,
+            products,
+            aliases: raw.aliases,
+            link: raw.surrounding_plan_link,
+            tariff_zones: raw.tariff_zones,
+            lines: Box::default(),
+        }
+    }
+}
+
+impl From<bgw_pt::Location> for Location {
+    fn from(raw: bgw_pt::Location) -> Self {
+        match raw {
+            bgw_pt::Location::Station(s) => Location::Station(s.into()),
+            bgw_pt::Location::Address(a) => Location::Address(Address::from_bgw_pt(a, false)),
+            bgw_pt::Location::Poi(a) => Location::Address(Address::from_bgw_pt(a, true)),
+        }
+    }
+}
+
+impl From<&bgw_pt::Stop> for Location {
+    /// a station if the stop has an id, its coordinates otherwise
+    fn from(stop: &bgw_pt::Stop) -> Self {
+        match &stop.station_global_id {
+            Some(id) => Location::Station(Station {
+                latitude: stop.latitude,
+                longitude: stop.longitude,
+                id: StationId::from_api(id.clone()),
+                diva_id: stop.station_diva_id.unwrap_or(0),
+                place: stop.place.clone(),
+                name: stop.name.clone(),
+                has_live_data: true,
+                has_zoom_data: stop.has_zoom_data,
+                products: stop.transport_types.iter().filter_map(|t| bgw_pt::product(t)).collect(),
+                aliases: None,
+                link: None,
+                tariff_zones: String::new(),
+                lines: Box::default(),
+            }),
+            None => Location::Location(Position {
+                latitude: stop.latitude as f32,
+                longitude: stop.longitude as f32,
+            }),
+        }
+    }
+}
+
+impl Address {
+    fn from_bgw_pt(raw: bgw_pt::Address, poi: bool) -> Self {
+        Address {
+            latitude: raw.latitude,
+            longitude: raw.longitude,
+            place: raw.place,
+            street: raw.name,
+            poi,
+        }
+    }
+}
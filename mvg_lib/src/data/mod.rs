@@ -1,6 +1,23 @@
 pub mod location;
 pub mod departure;
 pub mod connection;
+pub mod interruption;
+pub mod line;
+pub mod platform;
+pub mod station_id;
+pub(crate) mod bgw_pt;
+
+use std::fmt;
+
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::TIMEZONE;
+
+/// Time in Munich of the epoch millis used by the API
+pub(crate) fn from_millis(millis: u64) -> DateTime<Tz> {
+    TIMEZONE.timestamp_millis(millis as i64)
+}
 
 #[derive(Debug)]
 pub enum MVGError {
@@ -8,6 +25,108 @@ pub enum MVGError {
     JsonError(serde_json::Error),
     InvalidUri(http::uri::InvalidUri),
     ArgumentError(String),
+    /// The API answered with an unexpected HTTP status
+    HttpStatus {
+        url: String,
+        status: u16,
+        /// beginning of the response body
+        body: String,
+    },
+    /// The API answered with 404
+    NotFound { url: String },
+    /// The API didn't answer in time
+    Timeout { url: String },
+    /// The response couldn't be decoded
+    DecodeError {
+        url: String,
+        source: serde_json::Error,
+    },
+    /// A recorded response couldn't be read or written
+    Fixture {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    /// The transit provider doesn't offer this kind of request
+    Unsupported {
+        provider: &'static str,
+        request: &'static str,
+    },
+}
+
+/// Maximum number of characters of a response body kept in an error
+const BODY_EXCERPT_CHARS: usize = 200;
+
+impl MVGError {
+    /// Error for a non-successful HTTP status
+    pub fn from_status(url: &str, status: u16, body: &str) -> Self {
+        if status == 404 {
+            return MVGError::NotFound {
+                url: url.to_string(),
+            };
+        }
+        let mut excerpt: String = body.chars().take(BODY_EXCERPT_CHARS).collect();
+        if body.chars().count() > BODY_EXCERPT_CHARS {
+            excerpt.push_str("...");
+        }
+        MVGError::HttpStatus {
+            url: url.to_string(),
+            status,
+            body: excerpt,
+        }
+    }
+
+    /// URL of the failed request, if known
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            MVGError::HttpStatus { url, .. }
+            | MVGError::NotFound { url }
+            | MVGError::Timeout { url }
+            | MVGError::DecodeError { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MVGError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MVGError::HyperError(e) => write!(f, "couldn't connect to the MVG API: {}", e),
+            MVGError::JsonError(e) => write!(f, "couldn't parse JSON: {}", e),
+            MVGError::InvalidUri(e) => write!(f, "couldn't create valid URI: {}", e),
+            MVGError::ArgumentError(msg) => write!(f, "{}", msg),
+            MVGError::HttpStatus { url, status, body } => {
+                write!(f, "MVG API answered with HTTP {} for {}", status, url)?;
+                if !body.is_empty() {
+                    write!(f, ": {}", body)?;
+                }
+                Ok(())
+            }
+            MVGError::NotFound { url } => write!(f, "nothing found at {}", url),
+            MVGError::Timeout { url } => write!(f, "request to {} timed out", url),
+            MVGError::DecodeError { url, source } => {
+                write!(f, "couldn't parse API response from {}: {}", url, source)
+            }
+            MVGError::Fixture { path, source } => {
+                write!(f, "couldn't access fixture {}: {}", path.display(), source)
+            }
+            MVGError::Unsupported { provider, request } => {
+                write!(f, "{} isn't supported by {}", request, provider)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MVGError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MVGError::HyperError(e) => Some(e),
+            MVGError::JsonError(e) => Some(e),
+            MVGError::InvalidUri(e) => Some(e),
+            MVGError::DecodeError { source, .. } => Some(source),
+            MVGError::Fixture { source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 impl From<hyper::Error> for MVGError {
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// Platform or stop position of a departure, e.g. `2` or `Gleis 2`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Platform(String);
+
+impl Platform {
+    /// `None` for the empty string the API uses if there is no platform
+    pub(crate) fn from_api(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            None
+        } else {
+            Some(Platform(raw.to_string()))
+        }
+    }
+
+    /// platform as delivered by the API
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// number of the platform, if it has one (`Gleis 2` -> 2)
+    pub fn number(&self) -> Option<u32> {
+        let digits: String = self
+            .0
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    }
+
+    /// whether this is the platform given by the user, e.g. `2` matches `Gleis 2`
+    pub fn matches(&self, platform: &str) -> bool {
+        self.0.eq_ignore_ascii_case(platform.trim())
+            || (self.number().is_some() && self.number() == platform.trim().parse().ok())
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+/// Id of a station, either global like `de:09162:2` or a numeric DIVA id
+/// like `2`.
+///
+/// ```
+/// use mvg_lib::data::station_id::StationId;
+///
+/// let id: StationId = "de:09162:2".parse().unwrap();
+/// assert_eq!(id.diva_id(), Some(2));
+/// assert!("Marienplatz".parse::<StationId>().is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct StationId(String);
+
+impl StationId {
+    /// id as delivered by the API, which is trusted to be valid
+    pub(crate) fn from_api(id: String) -> Self {
+        StationId(id)
+    }
+
+    /// id as used by the API
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// whether this is a global id like `de:09162:2`
+    pub fn is_global(&self) -> bool {
+        self.0.contains(':')
+    }
+
+    /// the DIVA number, for global ids their last part
+    pub fn diva_id(&self) -> Option<u32> {
+        self.0.rsplit(':').next()?.parse().ok()
+    }
+}
+
+/// whether `s` is a non-empty run of ASCII digits
+fn digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+impl std::str::FromStr for StationId {
+    type Err = String;
+
+    /// Parse `de:09162:2` (country, municipality key and DIVA number) or a
+    /// DIVA number alone
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parts: Vec<&str> = s.split(':').collect();
+        let valid = match parts.as_slice() {
+            // there is no station 0, it stands for a missing DIVA id
+            [diva] => digits(diva) && diva.parse::<u32>().is_ok_and(|n| n != 0),
+            [country, municipality, diva] => {
+                country.len() == 2
+                    && country.chars().all(|c| c.is_ascii_alphabetic())
+                    && digits(municipality)
+                    && digits(diva)
+            }
+            _ => false,
+        };
+        if valid {
+            Ok(StationId(s.to_lowercase()))
+        } else {
+            Err(format!("Not a station id: {} (expected e.g. de:09162:2 or 2)", s))
+        }
+    }
+}
+
+impl std::fmt::Display for StationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for StationId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for StationId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for StationId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
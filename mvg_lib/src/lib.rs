@@ -1,107 +1,381 @@
+pub mod backend;
 pub mod data;
+pub mod provider;
 mod query;
+pub mod resolve;
+pub mod routing;
+pub mod station_index;
+pub mod time_format;
+pub mod transport;
 
 #[cfg(test)]
 mod test;
 
-use hyper::{body::HttpBody as _, client::HttpConnector, Client};
-use hyper_tls::HttpsConnector;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono_tz::Tz;
+use futures_util::future::join_all;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::de::DeserializeOwned;
 
-use data::{MVGError};
-use data::location::{Location, Locations};
-use data::departure::{Departure, DepartureInfo};
+use backend::Backend;
+use data::{bgw_pt, MVGError};
+use data::location::{Location, Locations, NearbyLocation};
+use data::departure::{self as departure, Departure, DepartureInfo, MultiDepartures, ServingLine};
+use data::station_id::StationId;
 use data::connection::{ConnectionList, Connection};
+use data::interruption::{Interruption, InterruptionList};
+use resolve::Resolution;
+use routing::RoutingOptions;
+use transport::{HttpsTransport, Transport};
+
+/// Base URL of the public MVG API
+pub const DEFAULT_BASE_URL: &str = "https://www.mvg.de";
+/// Timezone of Munich (`Europe/Berlin` in the tz database), all times
+/// returned by the library are in this zone
+pub const TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+/// Time after which a request is given up
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct MVG {
-    client: Client<HttpsConnector<HttpConnector>>,
+    transport: Box<dyn Transport>,
+    base_url: String,
+    timeout: Option<Duration>,
+    backend: Backend,
+    /// whether the bgw-pt API answered the last request of [`Backend::Auto`]
+    prefer_bgw_pt: AtomicBool,
+}
+
+/// Builder for a customized [`MVG`] client.
+pub struct MVGBuilder {
+    transport: Option<Box<dyn Transport>>,
+    base_url: String,
+    timeout: Option<Duration>,
+    backend: Backend,
+}
+
+impl MVGBuilder {
+    /// Use another server than the public MVG API, e.g. a local fixture server.
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Use a custom transport instead of the default HTTPS client.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Give up requests after `timeout`, `None` waits forever.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Talk to this API, [`Backend::Auto`] by default
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn build(self) -> MVG {
+        MVG {
+            transport: self
+                .transport
+                .unwrap_or_else(|| Box::new(HttpsTransport::new())),
+            base_url: self.base_url,
+            timeout: self.timeout,
+            backend: self.backend,
+            prefer_bgw_pt: AtomicBool::new(self.backend == Backend::BgwPt),
+        }
+    }
+}
+
+impl Default for MVGBuilder {
+    fn default() -> Self {
+        MVGBuilder {
+            transport: None,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            backend: Backend::default(),
+        }
+    }
 }
 
 impl MVG {
     pub fn new() -> Self {
-        let https = HttpsConnector::new();
-        let client = Client::builder().build::<_, hyper::Body>(https);
-        MVG { client: client }
+        Self::builder().build()
     }
 
-    pub async fn stations_by_name(&self, search: &str) -> Result<Vec<Location>, MVGError> {
-        let search = utf8_percent_encode(search, NON_ALPHANUMERIC).to_string();
-        let url = query::query_url_name(&search);
-        let url = url.parse::<hyper::Uri>()?;
+    pub fn builder() -> MVGBuilder {
+        MVGBuilder::default()
+    }
 
-        let mut res = self.client.get(url).await?;
+    /// Base URL all requests are sent to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
 
-        if res.status() != http::status::StatusCode::OK {
-            return Err(MVGError::ArgumentError("No response".to_string()));
-        }
-        let mut json = String::new();
-        while let Some(next) = res.data().await {
-            let chunk = next?;
-            json += &String::from_utf8_lossy(&chunk);
+    /// GET `url` and decode the JSON response
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, MVGError> {
+        let request = self.transport.get(url);
+        let res = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, request)
+                .await
+                .map_err(|_| MVGError::Timeout {
+                    url: url.to_string(),
+                })??,
+            None => request.await?,
+        };
+
+        if res.status != 200 {
+            return Err(MVGError::from_status(url, res.status, &res.body));
         }
+        serde_json::from_str(&res.body).map_err(|source| MVGError::DecodeError {
+            url: url.to_string(),
+            source,
+        })
+    }
 
-        let locations_raw: Locations = serde_json::from_str(&json)?;
-        Ok(locations_raw.locations)
+    /// The backend asked first, for [`Backend::Auto`] the one which answered last
+    pub fn active_backend(&self) -> Backend {
+        match self.backend {
+            Backend::Auto if self.prefer_bgw_pt.load(Ordering::Relaxed) => Backend::BgwPt,
+            Backend::Auto => Backend::Fahrinfo,
+            backend => backend,
+        }
     }
 
-    pub async fn stations_by_id(&self, id: &str) -> Result<Vec<Location>, MVGError> {
-        let url = query::query_url_id(id);
-        let url = url.parse::<hyper::Uri>()?;
+    /// Send `request` to the configured backend.
+    ///
+    /// [`Backend::Auto`] falls back to the other backend if the active one
+    /// answers with a server error or 404, and stays with it if it answers.
+    /// Other errors like timeouts or broken responses aren't retried. If both
+    /// fail, the error of the active one is returned.
+    async fn with_backend<T, F, Fut>(&self, request: F) -> Result<T, MVGError>
+    where
+        F: Fn(Backend) -> Fut,
+        Fut: Future<Output = Result<T, MVGError>>,
+    {
+        let active = self.active_backend();
+        let error = match request(active).await {
+            Ok(result) => return Ok(result),
+            Err(e) if self.backend != Backend::Auto || !unavailable(&e) => return Err(e),
+            Err(e) => e,
+        };
+        match request(active.other()).await {
+            Ok(result) => {
+                self.prefer_bgw_pt
+                    .store(active.other() == Backend::BgwPt, Ordering::Relaxed);
+                Ok(result)
+            }
+            Err(_) => Err(error),
+        }
+    }
 
-        let mut res = self.client.get(url).await?;
+    /// Locations whose name matches `search`. An empty `search` lists all
+    /// stations, only the fahrinfo API can do that, the bgw-pt API answers
+    /// with [`MVGError::Unsupported`].
+    pub async fn stations_by_name(&self, search: &str) -> Result<Vec<Location>, MVGError> {
+        let search = utf8_percent_encode(search, NON_ALPHANUMERIC).to_string();
+        self.with_backend(|backend| self.locations_by_name(backend, &search))
+            .await
+    }
 
-        if res.status() != http::status::StatusCode::OK {
-            return Err(MVGError::ArgumentError("No response".to_string()));
+    /// Locations matching the percent encoded `search`
+    async fn locations_by_name(&self, backend: Backend, search: &str) -> Result<Vec<Location>, MVGError> {
+        if backend == Backend::BgwPt {
+            // it only finds a few stations for an empty query
+            if search.is_empty() {
+                return Err(MVGError::Unsupported {
+                    provider: "the bgw-pt API",
+                    request: "listing all stations",
+                });
+            }
+            return self.bgw_pt_locations(search).await;
         }
-        let mut json = String::new();
-        while let Some(next) = res.data().await {
-            let chunk = next?;
-            json += &String::from_utf8_lossy(&chunk);
+        let url = query::query_url_name(&self.base_url, search);
+
+        let locations_raw: Locations = self.get_json(&url).await?;
+        Ok(locations_raw.locations)
+    }
+
+    /// The station with the given id, and possibly others the API finds for it
+    pub async fn stations_by_id(&self, id: &StationId) -> Result<Vec<Location>, MVGError> {
+        self.with_backend(|backend| self.locations_by_id(backend, id))
+            .await
+    }
+
+    async fn locations_by_id(&self, backend: Backend, id: &StationId) -> Result<Vec<Location>, MVGError> {
+        // valid ids don't need to be encoded
+        if backend == Backend::BgwPt {
+            return self.bgw_pt_locations(id.as_str()).await;
         }
+        let url = query::query_url_id(&self.base_url, id.as_str());
 
-        let locations_raw: Locations = serde_json::from_str(&json)?;
+        let locations_raw: Locations = self.get_json(&url).await?;
         Ok(locations_raw.locations)
     }
 
-    pub async fn departures_by_id(&self, station_id: &str) -> Result<Vec<Departure>, MVGError> {
-        let url: String = query::departure_url(station_id);
-        let url = url.parse::<hyper::Uri>()?;
+    async fn bgw_pt_locations(&self, query: &str) -> Result<Vec<Location>, MVGError> {
+        let url = query::bgw_pt_locations_url(&self.base_url, query);
+
+        let locations: Vec<bgw_pt::Location> = self.get_json(&url).await?;
+        Ok(locations.into_iter().map(Location::from).collect())
+    }
+
+    /// Search stations by name and pick the one meant by `query`,
+    /// see [`resolve::resolve_station`].
+    pub async fn resolve_station(&self, query: &str) -> Result<Resolution, MVGError> {
+        let locations = self.stations_by_name(query).await?;
+        Ok(resolve::resolve_station(query, locations))
+    }
 
-        let mut res = self.client.get(url).await?;
+    /// Stations and other locations around the given coordinates, closest first
+    pub async fn stations_nearby(&self, latitude: f64, longitude: f64) -> Result<Vec<NearbyLocation>, MVGError> {
+        let locations = self
+            .with_backend(|backend| self.locations_nearby(backend, latitude, longitude))
+            .await?;
+        let mut nearby: Vec<NearbyLocation> = locations
+            .into_iter()
+            .map(|l| NearbyLocation::new(l, latitude, longitude))
+            .collect();
+        nearby.sort_by(|a, b| a.distance().partial_cmp(&b.distance()).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(nearby)
+    }
 
-        if res.status() != http::status::StatusCode::OK {
-            return Err(MVGError::ArgumentError(
-                format!("No valid station id: {}", station_id)
-            ));
+    async fn locations_nearby(&self, backend: Backend, latitude: f64, longitude: f64) -> Result<Vec<Location>, MVGError> {
+        if backend == Backend::BgwPt {
+            let url = query::bgw_pt_nearby_url(&self.base_url, latitude, longitude);
+            let stations: Vec<bgw_pt::Station> = self.get_json(&url).await?;
+            return Ok(stations.into_iter().map(|s| Location::Station(s.into())).collect());
         }
-        let mut json = String::new();
-        while let Some(next) = res.data().await {
-            let chunk = next?;
-            json += &String::from_utf8_lossy(&chunk);
+        let url = query::nearby_url(&self.base_url, latitude, longitude);
+
+        let locations_raw: Locations = self.get_json(&url).await?;
+        Ok(locations_raw.locations)
+    }
+
+    pub async fn departures_by_id(&self, station_id: &StationId) -> Result<Vec<Departure>, MVGError> {
+        self.with_backend(|backend| self.departures_from(backend, station_id))
+            .await
+    }
+
+    async fn departures_from(&self, backend: Backend, station_id: &StationId) -> Result<Vec<Departure>, MVGError> {
+        if backend == Backend::BgwPt {
+            let url = query::bgw_pt_departure_url(&self.base_url, station_id.as_str());
+            let departures: Vec<bgw_pt::Departure> = self.get_json(&url).await?;
+            return Ok(departures.into_iter().map(Departure::from_bgw_pt).collect());
         }
-        let departure_info: DepartureInfo = serde_json::from_str(&json)?;
+        let url = query::departure_url(&self.base_url, station_id.as_str());
+
+        let departure_info: DepartureInfo = self.get_json(&url).await?;
         Ok(departure_info.departures)
     }
 
-    pub async fn connections(&self, from_id: &str, to_id: &str) -> Result<Vec<Connection>, MVGError>{
-        let url = query::routing_url(from_id, to_id);
-        let url = url.parse::<hyper::Uri>()?;
+    /// The lines departing at a station, once per line and destination
+    pub async fn serving_lines(&self, station_id: &StationId) -> Result<Vec<ServingLine>, MVGError> {
+        self.with_backend(|backend| self.serving_lines_from(backend, station_id))
+            .await
+    }
+
+    async fn serving_lines_from(&self, backend: Backend, station_id: &StationId) -> Result<Vec<ServingLine>, MVGError> {
+        if backend == Backend::BgwPt {
+            // the API only lists the lines of the next departures
+            let departures = self.departures_from(backend, station_id).await?;
+            return Ok(departure::serving_lines(&departures));
+        }
+        let url = query::departure_url(&self.base_url, station_id.as_str());
 
-        let mut res = self.client.get(url).await?;
+        let departure_info: DepartureInfo = self.get_json(&url).await?;
+        Ok(departure_info.serving_lines)
+    }
 
-        if res.status() != http::status::StatusCode::OK {
-            return Err(MVGError::ArgumentError(
-                format!("No valid station ids: {} - {}", from_id, to_id)
-            ));
+    /// Departures of several stations, fetched concurrently and merged into
+    /// one list sorted by departure time.
+    ///
+    /// Stations whose departures can't be fetched don't fail the whole
+    /// call, their errors are part of the result instead.
+    pub async fn departures_multi(&self, station_ids: &[StationId]) -> MultiDepartures {
+        let requests = station_ids
+            .iter()
+            .map(|id| async move { (id.clone(), self.departures_by_id(id).await) });
+        MultiDepartures::new(join_all(requests).await)
+    }
+
+    pub async fn connections(&self, from_id: &StationId, to_id: &StationId) -> Result<Vec<Connection>, MVGError>{
+        self.connections_with(from_id, to_id, &RoutingOptions::default()).await
+    }
+
+    /// Search connections with additional routing options, options which
+    /// exclude every product are an [`MVGError::ArgumentError`]
+    pub async fn connections_with(
+        &self,
+        from_id: &StationId,
+        to_id: &StationId,
+        options: &RoutingOptions,
+    ) -> Result<Vec<Connection>, MVGError> {
+        if !options.allows_any() {
+            return Err(MVGError::ArgumentError(String::from(
+                "every product is excluded, there are no connections",
+            )));
         }
-        let mut json = String::new();
-        while let Some(next) = res.data().await {
-            let chunk = next?;
-            json += &String::from_utf8_lossy(&chunk);
+        self.with_backend(|backend| self.connections_from(backend, from_id, to_id, options))
+            .await
+    }
+
+    /// The `options` ignored by the backend which answered the last request,
+    /// for [`Backend::Auto`] that may be the bgw-pt API after a fallback
+    pub fn ignored_routing_options(&self, options: &RoutingOptions) -> Vec<&'static str> {
+        match self.active_backend() {
+            Backend::BgwPt => options.ignored_by_bgw_pt(),
+            _ => Vec::new(),
+        }
+    }
+
+    async fn connections_from(
+        &self,
+        backend: Backend,
+        from_id: &StationId,
+        to_id: &StationId,
+        options: &RoutingOptions,
+    ) -> Result<Vec<Connection>, MVGError> {
+        if backend == Backend::BgwPt {
+            let url = query::bgw_pt_routes_url(&self.base_url, from_id.as_str(), to_id.as_str(), options);
+            let connections: Vec<bgw_pt::Connection> = self.get_json(&url).await?;
+            return Ok(connections.into_iter().filter_map(Connection::from_bgw_pt).collect());
         }
-        let connections: ConnectionList = serde_json::from_str(&json)?;
+        let url = query::routing_url(&self.base_url, from_id.as_str(), to_id.as_str(), options);
+
+        let connections: ConnectionList = self.get_json(&url).await?;
         let connections = connections.connection_list;
         Ok(connections)
     }
+
+    /// Current service interruptions and planned changes of service
+    pub async fn interruptions(&self) -> Result<Vec<Interruption>, MVGError> {
+        let url = query::interruptions_url(&self.base_url);
+
+        let interruptions: InterruptionList = self.get_json(&url).await?;
+        Ok(interruptions.interruption)
+    }
 }
 
+impl Default for MVG {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `error` means the backend can't answer the request at all, so
+/// asking the other one makes sense
+fn unavailable(error: &MVGError) -> bool {
+    match error {
+        MVGError::HttpStatus { status, .. } => *status >= 500,
+        MVGError::NotFound { .. } | MVGError::Unsupported { .. } => true,
+        _ => false,
+    }
+}
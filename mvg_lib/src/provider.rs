@@ -0,0 +1,158 @@
+//! Transit networks behind a common interface.
+//!
+//! [`MVG`] is one [`TransitProvider`], other networks running the EFA
+//! software can be added by implementing the trait and a [`Provider`]
+//! variant for them.
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::data::connection::Connection;
+use crate::data::departure::{self, Departure, MultiDepartures, ServingLine};
+use crate::data::interruption::Interruption;
+use crate::data::location::{Location, NearbyLocation};
+use crate::data::station_id::StationId;
+use crate::data::MVGError;
+use crate::resolve::{self, Resolution};
+use crate::routing::RoutingOptions;
+use crate::MVG;
+
+/// Requests the command line tool sends to a transit network.
+///
+/// Only searching locations, departures and routes are required, the
+/// other requests fail with [`MVGError::Unsupported`] unless implemented.
+#[async_trait]
+pub trait TransitProvider: Send + Sync {
+    /// Short name of the network, e.g. `mvg`
+    fn name(&self) -> &'static str;
+
+    async fn stations_by_name(&self, search: &str) -> Result<Vec<Location>, MVGError>;
+
+    async fn stations_by_id(&self, id: &StationId) -> Result<Vec<Location>, MVGError>;
+
+    async fn departures_by_id(&self, station_id: &StationId) -> Result<Vec<Departure>, MVGError>;
+
+    async fn connections_with(
+        &self,
+        from_id: &StationId,
+        to_id: &StationId,
+        options: &RoutingOptions,
+    ) -> Result<Vec<Connection>, MVGError>;
+
+    /// The `options` the last connection search couldn't honor, see
+    /// [`RoutingOptions::ignored_by_bgw_pt`]
+    fn ignored_routing_options(&self, _options: &RoutingOptions) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Stations and other locations around the given coordinates, closest first
+    async fn stations_nearby(&self, _latitude: f64, _longitude: f64) -> Result<Vec<NearbyLocation>, MVGError> {
+        Err(MVGError::Unsupported {
+            provider: self.name(),
+            request: "searching nearby stations",
+        })
+    }
+
+    /// Current service interruptions and planned changes of service
+    async fn interruptions(&self) -> Result<Vec<Interruption>, MVGError> {
+        Err(MVGError::Unsupported {
+            provider: self.name(),
+            request: "listing interruptions",
+        })
+    }
+
+    /// The lines departing at a station, by default the ones of its next
+    /// departures
+    async fn serving_lines(&self, station_id: &StationId) -> Result<Vec<ServingLine>, MVGError> {
+        let departures = self.departures_by_id(station_id).await?;
+        Ok(departure::serving_lines(&departures))
+    }
+
+    /// Search stations by name and pick the one meant by `query`,
+    /// see [`resolve::resolve_station`].
+    async fn resolve_station(&self, query: &str) -> Result<Resolution, MVGError> {
+        let locations = self.stations_by_name(query).await?;
+        Ok(resolve::resolve_station(query, locations))
+    }
+
+    /// Departures of several stations, see [`MVG::departures_multi`]
+    async fn departures_multi(&self, station_ids: &[StationId]) -> MultiDepartures {
+        let requests = station_ids
+            .iter()
+            .map(|id| async move { (id.clone(), self.departures_by_id(id).await) });
+        MultiDepartures::new(join_all(requests).await)
+    }
+}
+
+#[async_trait]
+impl TransitProvider for MVG {
+    fn name(&self) -> &'static str {
+        "mvg"
+    }
+
+    async fn stations_by_name(&self, search: &str) -> Result<Vec<Location>, MVGError> {
+        MVG::stations_by_name(self, search).await
+    }
+
+    async fn stations_by_id(&self, id: &StationId) -> Result<Vec<Location>, MVGError> {
+        MVG::stations_by_id(self, id).await
+    }
+
+    async fn departures_by_id(&self, station_id: &StationId) -> Result<Vec<Departure>, MVGError> {
+        MVG::departures_by_id(self, station_id).await
+    }
+
+    async fn connections_with(
+        &self,
+        from_id: &StationId,
+        to_id: &StationId,
+        options: &RoutingOptions,
+    ) -> Result<Vec<Connection>, MVGError> {
+        MVG::connections_with(self, from_id, to_id, options).await
+    }
+
+    fn ignored_routing_options(&self, options: &RoutingOptions) -> Vec<&'static str> {
+        MVG::ignored_routing_options(self, options)
+    }
+
+    async fn serving_lines(&self, station_id: &StationId) -> Result<Vec<ServingLine>, MVGError> {
+        MVG::serving_lines(self, station_id).await
+    }
+
+    async fn stations_nearby(&self, latitude: f64, longitude: f64) -> Result<Vec<NearbyLocation>, MVGError> {
+        MVG::stations_nearby(self, latitude, longitude).await
+    }
+
+    async fn interruptions(&self) -> Result<Vec<Interruption>, MVGError> {
+        MVG::interruptions(self).await
+    }
+}
+
+/// The transit networks available
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    /// Münchner Verkehrsgesellschaft, Munich
+    #[default]
+    Mvg,
+}
+
+impl std::str::FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mvg" => Ok(Provider::Mvg),
+            _ => Err(format!("Unknown provider: {} (expected mvg)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::Mvg => f.write_str("mvg"),
+        }
+    }
+}
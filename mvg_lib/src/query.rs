@@ -1,53 +1,75 @@
+use crate::routing::RoutingOptions;
+
 // source of urls: https://github.com/leftshift/python_mvg_api/blob/master/mvg_api/__init__.py
 
 /// Generate URL to query stations by name
 #[allow(dead_code)]
-pub fn query_url_name(name: &str) -> String {
-    format!(
-        "https://www.mvg.de/api/fahrinfo/location/queryWeb?q={}",
-        name
-    )
+pub fn query_url_name(base: &str, name: &str) -> String {
+    format!("{}/api/fahrinfo/location/queryWeb?q={}", base, name)
 }
 /// Generate URL to query station by id
-#[allow(dead_code)]
-pub fn query_url_id(id: &str) -> String {
-    format!("https://www.mvg.de/api/fahrinfo/departure/{}?footway=0", id)
+pub fn query_url_id(base: &str, id: &str) -> String {
+    format!("{}/api/fahrinfo/location/query?q={}", base, id)
 }
 /// Generate URL to query departures by station id
 #[allow(dead_code)]
-pub fn departure_url(id: &str) -> String {
-    format!("https://www.mvg.de/api/fahrinfo/departure/{}?footway=0", id)
+pub fn departure_url(base: &str, id: &str) -> String {
+    format!("{}/api/fahrinfo/departure/{}?footway=0", base, id)
 }
 /// Generate URL to query nearby stations
 #[allow(dead_code)]
-pub fn nearby_url(lat: f64, lon: f64) -> String {
+pub fn nearby_url(base: &str, lat: f64, lon: f64) -> String {
     format!(
-        "https://www.mvg.de/api/fahrinfo/location/nearby?latitude={}&longitude={}",
-        lat, lon
+        "{}/api/fahrinfo/location/nearby?latitude={}&longitude={}",
+        base, lat, lon
     )
 }
 /// Generate URL to query routing info
 #[allow(dead_code)]
-pub fn routing_url(from_id: &str, to_id: &str) -> String {
+pub fn routing_url(base: &str, from_id: &str, to_id: &str, routing_options: &RoutingOptions) -> String {
     let mut options = Vec::new();
     options.push(format!("fromStation={}", from_id));
     options.push(format!("toStation={}", to_id));
-    /* Possible other options:
-    options.push(format!("time={}", time));
-    options.push(format!("arrival={}", true));
-    options.push(format!("maxTravelTimeFootwayToStation={}", 60));
-    options.push(format!("maxTravelTimeFootwayToDestination={}", 60));
-    options.push(format!("changeLimit={}", 5));
-    options.push(String::from("transportTypeUnderground=false"));
-    options.push(String::from("transportTypeBus=false"));
-    options.push(String::from("transportTypeTram=false"));
-    options.push(String::from("transportTypeSBahn=false"));
-    */
+    options.extend(routing_options.query_params());
     let options = options.join("&");
-    format!("https://www.mvg.de/api/fahrinfo/routing/?{}", options)
+    format!("{}/api/fahrinfo/routing/?{}", base, options)
 }
 /// Generate URL to query interruptions
 #[allow(dead_code)]
-pub fn interruptions_url() -> String {
-    String::from("https://www.mvg.de/.rest/betriebsaenderungen/api/interruptions")
+pub fn interruptions_url(base: &str) -> String {
+    format!("{}/.rest/betriebsaenderungen/api/interruptions", base)
+}
+
+/// Transport types requested from the bgw-pt API, the ones with a [`Product`](crate::data::connection::Product)
+const BGW_PT_TRANSPORT_TYPES: &str = "UBAHN,TRAM,SBAHN,BUS,REGIONAL_BUS,BAHN";
+
+/// Generate URL to query locations by name or id with the bgw-pt API
+pub fn bgw_pt_locations_url(base: &str, query: &str) -> String {
+    format!(
+        "{}/api/bgw-pt/v3/locations?query={}&locationTypes=STATION,ADDRESS,POI",
+        base, query
+    )
+}
+/// Generate URL to query departures by station id with the bgw-pt API
+pub fn bgw_pt_departure_url(base: &str, id: &str) -> String {
+    format!(
+        "{}/api/bgw-pt/v3/departures?globalId={}&transportTypes={}",
+        base, id, BGW_PT_TRANSPORT_TYPES
+    )
+}
+/// Generate URL to query nearby stations with the bgw-pt API
+pub fn bgw_pt_nearby_url(base: &str, lat: f64, lon: f64) -> String {
+    format!(
+        "{}/api/bgw-pt/v3/stations/nearby?latitude={}&longitude={}",
+        base, lat, lon
+    )
+}
+/// Generate URL to query routes with the bgw-pt API
+pub fn bgw_pt_routes_url(base: &str, from_id: &str, to_id: &str, routing_options: &RoutingOptions) -> String {
+    let mut options = Vec::new();
+    options.push(format!("originStationGlobalId={}", from_id));
+    options.push(format!("destinationStationGlobalId={}", to_id));
+    options.extend(routing_options.bgw_pt_query_params());
+    let options = options.join("&");
+    format!("{}/api/bgw-pt/v3/routes?{}", base, options)
 }
@@ -0,0 +1,234 @@
+use crate::data::location::{Location, Station};
+
+/// Result of resolving a station name
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Resolution {
+    /// exactly one station fits best
+    Unique(Station),
+    /// several stations fit equally well, best candidates first
+    Ambiguous(Vec<Station>),
+    NotFound,
+}
+
+/// How well a station name matches the query, better matches are greater
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NameMatch {
+    None,
+    /// a word of the name starts like the query, apart from a few typos
+    Similar,
+    Contains,
+    Prefix,
+    Exact,
+}
+
+/// A station with its rank for a query
+struct Candidate {
+    station: Station,
+    name_match: NameMatch,
+    score: u32,
+}
+
+/// Place of the MVG's own network, preferred over stations of the surrounding area
+const HOME_PLACE: &str = "München";
+
+/// Rank the stations in `locations` for `query`, best match first.
+///
+/// The query may be a station name or `name, place` like
+/// `Marienplatz, München`. Stations are ranked by how well their name
+/// matches, then by whether their place matches, then by the number of
+/// products serving them. Other locations are dropped.
+pub fn rank_stations(query: &str, locations: Vec<Location>) -> Vec<Station> {
+    rank(query, stations(locations)).into_iter().map(|c| c.station).collect()
+}
+
+/// The stations whose name matches `query` at least with a few typos, best
+/// match first, ranked like [`rank_stations`]
+pub fn search_stations(query: &str, stations: Vec<Station>) -> Vec<Station> {
+    rank(query, stations)
+        .into_iter()
+        .filter(|c| c.name_match != NameMatch::None)
+        .map(|c| c.station)
+        .collect()
+}
+
+/// Pick the station meant by `query` from `locations`.
+///
+/// The result is ambiguous if several stations have the best kind of name
+/// match (exact, prefix or substring), e.g. `Marienplatz` in München and
+/// in Oberalting. Adding the place to the query (`Marienplatz, München`)
+/// makes it unique.
+pub fn resolve_station(query: &str, locations: Vec<Location>) -> Resolution {
+    pick(rank(query, stations(locations)))
+}
+
+/// Resolve `query` among known `stations` without asking the API.
+///
+/// `None` if the stations can't tell: none matches, or they are only some
+/// of the network's stations (`complete` is false) and the query doesn't
+/// name exactly one of them with its place, so the API might know other
+/// stations of the same name.
+pub(crate) fn resolve_offline(query: &str, stations: Vec<Station>, complete: bool) -> Option<Resolution> {
+    let candidates: Vec<Candidate> = rank(query, stations)
+        .into_iter()
+        .filter(|c| c.name_match != NameMatch::None)
+        .collect();
+    let best = candidates.first()?.name_match;
+    if complete {
+        return Some(pick(candidates));
+    }
+    if best != NameMatch::Exact || split_query(query).1.is_none() {
+        return None;
+    }
+    match pick(candidates) {
+        Resolution::Unique(station) => Some(Resolution::Unique(station)),
+        _ => None,
+    }
+}
+
+/// The resolution of ranked candidates
+fn pick(mut candidates: Vec<Candidate>) -> Resolution {
+    if candidates.is_empty() {
+        return Resolution::NotFound;
+    }
+    let best = candidates[0].name_match;
+    let equally_good = candidates
+        .iter()
+        .take_while(|c| c.name_match == best)
+        .count();
+    if candidates.len() == 1 || (best != NameMatch::None && equally_good == 1) {
+        return Resolution::Unique(candidates.remove(0).station);
+    }
+    Resolution::Ambiguous(candidates.into_iter().map(|c| c.station).collect())
+}
+
+fn stations(locations: Vec<Location>) -> Vec<Station> {
+    locations
+        .into_iter()
+        .filter_map(|l| match l {
+            Location::Station(s) => Some(s),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Folded name and place of a `name, place` query
+fn split_query(query: &str) -> (String, Option<String>) {
+    match query.rfind(',') {
+        Some(i) => (fold(&query[..i]), Some(fold(&query[i + 1..]))),
+        None => (fold(query), None),
+    }
+}
+
+fn rank(query: &str, stations: Vec<Station>) -> Vec<Candidate> {
+    let (name, place) = split_query(query);
+
+    let mut candidates: Vec<Candidate> = stations
+        .into_iter()
+        .filter(|s| place.as_ref().is_none_or(|p| fold(&s.place()).contains(p.as_str())))
+        .map(|station| {
+            let station_name = fold(&station.name());
+            let name_match = if station_name == name {
+                NameMatch::Exact
+            } else if station_name.starts_with(&name) {
+                NameMatch::Prefix
+            } else if station_name.contains(&name) {
+                NameMatch::Contains
+            } else if similar(&name, &station_name) {
+                NameMatch::Similar
+            } else {
+                NameMatch::None
+            };
+            let mut score = station.products().len() as u32;
+            if station.place() == HOME_PLACE {
+                score += 10;
+            }
+            Candidate {
+                station,
+                name_match,
+                score,
+            }
+        })
+        .collect();
+    // stable sort keeps the API's order for equal candidates
+    candidates.sort_by(|a, b| {
+        b.name_match
+            .cmp(&a.name_match)
+            .then(b.score.cmp(&a.score))
+    });
+    candidates
+}
+
+/// A name as compared when resolving: lowercase, umlauts spelled out
+/// (`ü` → `ue`, `ß` → `ss`), `straße` abbreviated to `str` and punctuation
+/// replaced by single spaces.
+///
+/// ```
+/// use mvg_lib::resolve::fold;
+///
+/// assert_eq!(fold("Theatinerstraße"), fold("theatinerstr."));
+/// assert_eq!(fold("Münchner Freiheit"), "muenchner freiheit");
+/// ```
+pub fn fold(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.to_lowercase().chars() {
+        match c {
+            'ä' => folded.push_str("ae"),
+            'ö' => folded.push_str("oe"),
+            'ü' => folded.push_str("ue"),
+            'ß' => folded.push_str("ss"),
+            c if c.is_alphanumeric() => folded.push(c),
+            _ => folded.push(' '),
+        }
+    }
+    folded
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("strasse", "str")
+}
+
+/// Typos tolerated in a query of `len` characters
+fn allowed_typos(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// whether a word of `name` starts like `query` apart from a few typos
+fn similar(query: &str, name: &str) -> bool {
+    let query: Vec<char> = query.chars().collect();
+    let max = allowed_typos(query.len());
+    if max == 0 {
+        return false;
+    }
+    let name: Vec<char> = name.chars().collect();
+    (0..name.len())
+        .filter(|&i| i == 0 || name[i - 1] == ' ')
+        .any(|start| prefix_distance(&query, &name[start..]) <= max)
+}
+
+/// Edit distance between `query` and the closest prefix of `text`, swapped
+/// neighbours count as one edit
+fn prefix_distance(query: &[char], text: &[char]) -> usize {
+    // d[i][j]: distance between the first i chars of query and of text
+    let mut d = vec![vec![0; text.len() + 1]; query.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=text.len()).collect();
+    for i in 1..=query.len() {
+        for j in 1..=text.len() {
+            let cost = usize::from(query[i - 1] != text[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && query[i - 1] == text[j - 2] && query[i - 2] == text[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[query.len()].iter().copied().min().unwrap_or(query.len())
+}
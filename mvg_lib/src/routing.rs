@@ -0,0 +1,169 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::data::connection::Product;
+
+/// the products routes can be asked for, in the order of the bgw-pt API
+const ROUTED_PRODUCTS: [Product; 5] =
+    [Product::UBahn, Product::Tram, Product::SBahn, Product::Bus, Product::Bahn];
+
+/// Options for a connection search, see [`MVG::connections_with`](crate::MVG::connections_with).
+///
+/// The bgw-pt API doesn't support limiting changes and walking times, these
+/// limits only apply to the fahrinfo API.
+///
+/// ```
+/// use mvg_lib::routing::RoutingOptions;
+/// use mvg_lib::data::connection::Product;
+///
+/// let options = RoutingOptions::new().max_changes(2).exclude(Product::Bus);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RoutingOptions {
+    /// epoch millis and whether they denote the arrival
+    time: Option<(i64, bool)>,
+    max_changes: Option<u8>,
+    max_walk_to_station: Option<u32>,
+    max_walk_to_destination: Option<u32>,
+    excluded: Vec<Product>,
+}
+
+impl RoutingOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Search connections departing at the given time
+    pub fn depart_at<Tz: TimeZone>(mut self, time: DateTime<Tz>) -> Self {
+        self.time = Some((time.timestamp_millis(), false));
+        self
+    }
+
+    /// Search connections arriving at the given time
+    pub fn arrive_by<Tz: TimeZone>(mut self, time: DateTime<Tz>) -> Self {
+        self.time = Some((time.timestamp_millis(), true));
+        self
+    }
+
+    /// Maximum number of changes between products
+    pub fn max_changes(mut self, changes: u8) -> Self {
+        self.max_changes = Some(changes);
+        self
+    }
+
+    /// Maximum walking time in minutes to the first station
+    pub fn max_walk_to_station(mut self, minutes: u32) -> Self {
+        self.max_walk_to_station = Some(minutes);
+        self
+    }
+
+    /// Maximum walking time in minutes from the last station to the destination
+    pub fn max_walk_to_destination(mut self, minutes: u32) -> Self {
+        self.max_walk_to_destination = Some(minutes);
+        self
+    }
+
+    /// Don't use the given product
+    pub fn exclude(mut self, product: Product) -> Self {
+        if !self.excluded.contains(&product) {
+            self.excluded.push(product);
+        }
+        self
+    }
+
+    /// Only use the given products.
+    ///
+    /// The fahrinfo API has no switch for regional trains, it always allows
+    /// [`Product::Bahn`]. So `only(&[Product::Bahn])` just excludes the other
+    /// products there, and regional trains are used even if `products`
+    /// doesn't contain them. The bgw-pt API honors both.
+    pub fn only(mut self, products: &[Product]) -> Self {
+        self.excluded = ROUTED_PRODUCTS
+            .iter()
+            .filter(|p| !products.contains(p))
+            .copied()
+            .collect();
+        self
+    }
+
+    /// whether the product may be used
+    pub fn allows(&self, product: Product) -> bool {
+        !self.excluded.contains(&product)
+    }
+
+    /// whether any product is left to route with
+    pub fn allows_any(&self) -> bool {
+        ROUTED_PRODUCTS.iter().any(|p| self.allows(*p))
+    }
+
+    /// The options the bgw-pt API has no parameter for, they only work with
+    /// the fahrinfo API
+    pub fn ignored_by_bgw_pt(&self) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        if self.max_walk_to_station.is_some() {
+            ignored.push("maximum walking time to the station");
+        }
+        if self.max_walk_to_destination.is_some() {
+            ignored.push("maximum walking time to the destination");
+        }
+        if self.max_changes.is_some() {
+            ignored.push("maximum number of changes");
+        }
+        ignored
+    }
+
+    /// query parameters understood by the routing endpoint
+    pub(crate) fn query_params(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some((time, arrival)) = self.time {
+            options.push(format!("time={}", time));
+            if arrival {
+                options.push(String::from("arrival=true"));
+            }
+        }
+        if let Some(minutes) = self.max_walk_to_station {
+            options.push(format!("maxTravelTimeFootwayToStation={}", minutes));
+        }
+        if let Some(minutes) = self.max_walk_to_destination {
+            options.push(format!("maxTravelTimeFootwayToDestination={}", minutes));
+        }
+        if let Some(changes) = self.max_changes {
+            options.push(format!("changeLimit={}", changes));
+        }
+        for product in self.excluded.iter() {
+            let switch = match product {
+                Product::UBahn => "transportTypeUnderground",
+                Product::Bus => "transportTypeBus",
+                Product::Tram => "transportTypeTram",
+                Product::SBahn => "transportTypeSBahn",
+                // there is no switch for regional trains or unknown products
+                Product::Bahn | Product::Other => continue,
+            };
+            options.push(format!("{}=false", switch));
+        }
+        options
+    }
+
+    /// query parameters understood by the routes endpoint of the bgw-pt API
+    pub(crate) fn bgw_pt_query_params(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some((time, arrival)) = self.time {
+            let time = Utc.timestamp_millis(time);
+            options.push(format!("routingDateTime={}", time.format("%Y-%m-%dT%H:%M:%S%.3fZ")));
+            options.push(format!("routingDateTimeIsArrival={}", arrival));
+        }
+        let types: Vec<&str> = ROUTED_PRODUCTS
+            .iter()
+            .filter(|p| self.allows(**p))
+            .flat_map(|p| match p {
+                Product::Bus => vec!["BUS", "REGIONAL_BUS"],
+                Product::UBahn => vec!["UBAHN"],
+                Product::Tram => vec!["TRAM"],
+                Product::SBahn => vec!["SBAHN"],
+                Product::Bahn => vec!["BAHN"],
+                Product::Other => vec![],
+            })
+            .collect();
+        options.push(format!("transportTypes={}", types.join(",")));
+        options
+    }
+}
@@ -0,0 +1,132 @@
+//! Stations known without asking the API, to resolve station names offline.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::location::{Location, Station};
+use crate::data::station_id::StationId;
+use crate::resolve::{self, Resolution};
+
+/// Stations collected from API responses, or all stations of the network
+/// after [`StationIndex::complete`].
+///
+/// ```
+/// use mvg_lib::station_index::StationIndex;
+///
+/// let index = StationIndex::new();
+/// assert!(index.resolve("Marienplatz").is_none());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct StationIndex {
+    /// whether these are all stations of the network
+    #[serde(default)]
+    complete: bool,
+    stations: Vec<Station>,
+}
+
+impl StationIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of all stations of the network, e.g. from `stations_by_name("")`
+    pub fn complete(locations: &[Location]) -> Self {
+        let mut index = StationIndex::new();
+        index.merge(locations);
+        index.complete = true;
+        index
+    }
+
+    /// Read an index written by [`StationIndex::save`], a missing file is an
+    /// empty index
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(io::Error::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the index to `path`, creating its directory
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // readers never see a half written index
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string(self)?)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// whether all stations of the network are known
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn stations(&self) -> &[Station] {
+        &self.stations
+    }
+
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+
+    /// Add the stations among `locations`, replacing known ones with the
+    /// same id. Returns whether the index changed.
+    pub fn merge(&mut self, locations: &[Location]) -> bool {
+        let mut positions: HashMap<StationId, usize> = self
+            .stations
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.id().clone(), i))
+            .collect();
+        let mut changed = false;
+        for location in locations {
+            let station = match location {
+                Location::Station(s) => s,
+                _ => continue,
+            };
+            match positions.get(station.id()) {
+                Some(&i) if self.stations[i] == *station => {}
+                Some(&i) => {
+                    self.stations[i] = station.clone();
+                    changed = true;
+                }
+                None => {
+                    positions.insert(station.id().clone(), self.stations.len());
+                    self.stations.push(station.clone());
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// The station with `id`, see [`Station::has_id`]
+    pub fn by_id(&self, id: &StationId) -> Option<&Station> {
+        self.stations.iter().find(|s| s.has_id(id))
+    }
+
+    /// Stations whose name matches `query`, see [`resolve::search_stations`]
+    pub fn search(&self, query: &str) -> Vec<Station> {
+        resolve::search_stations(query, self.stations.clone())
+    }
+
+    /// Resolve `query` like [`resolve::resolve_station`], but offline.
+    ///
+    /// Names are compared ignoring umlauts, `straße`/`str.` and a few
+    /// typos. `None` if the index can't tell: no station matches, or the
+    /// index isn't complete and the query isn't the exact name and place
+    /// (`Marienplatz, München`) of a single station.
+    pub fn resolve(&self, query: &str) -> Option<Resolution> {
+        resolve::resolve_offline(query, self.stations.clone(), self.complete)
+    }
+}
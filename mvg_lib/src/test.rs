@@ -1,24 +1,1044 @@
-use crate::data::location::Location;
-use crate::MVG;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+
+use crate::backend::Backend;
+use crate::data::connection::{Connection, ConnectionList, ConnectionPart, Product};
+use crate::data::departure::{Departure, DepartureFilter};
+use crate::data::line::{LineGroup, Lines};
+use crate::data::location::{distance, Location, Locations};
+use crate::data::platform::Platform;
+use crate::data::station_id::StationId;
+use crate::data::MVGError;
+use crate::provider::{Provider, TransitProvider};
+use crate::query;
+use crate::resolve::{fold, resolve_station, Resolution};
+use crate::station_index::StationIndex;
+use crate::routing::RoutingOptions;
+use crate::time_format::{format_delay, DisplayZone, FixedClock, TimeFormat, TimeFormatter};
+use crate::transport::{
+    fixture_name, HttpsTransport, RecordingTransport, ReplayTransport, Response, Transport,
+};
+use crate::{MVG, TIMEZONE};
+
+const BASE_URL: &str = "http://localhost:8080";
+
+/// Answers requests from memory, keyed by the full request URL
+#[derive(Default)]
+struct FakeTransport {
+    responses: HashMap<String, Response>,
+    /// URLs requested so far
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeTransport {
+    fn with(mut self, path: &str, status: u16, body: &str) -> Self {
+        self.responses
+            .insert(format!("{}{}", BASE_URL, path), Response::new(status, body));
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for FakeTransport {
+    async fn get(&self, url: &str) -> Result<Response, MVGError> {
+        self.requests.lock().unwrap().push(url.to_string());
+        Ok(self
+            .responses
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| Response::new(404, "")))
+    }
+}
+
+/// Directory of the recorded API responses
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+/// MVG answering from the recorded fixtures
+fn fake_mvg() -> MVG {
+    MVG::builder()
+        .base_url(BASE_URL)
+        .transport(ReplayTransport::new(FIXTURES))
+        .build()
+}
+
+fn station_id(id: &str) -> StationId {
+    id.parse().unwrap()
+}
+
+/// MVG answering from the recorded fixtures of the bgw-pt API
+fn bgw_pt_mvg() -> MVG {
+    MVG::builder()
+        .base_url(BASE_URL)
+        .transport(ReplayTransport::new(FIXTURES))
+        .backend(Backend::BgwPt)
+        .build()
+}
+
+/// MVG answering from the recorded fixtures, or from the live API with
+/// `MVG_RECORD=1`, updating the fixtures
+fn recorded_mvg() -> MVG {
+    if std::env::var_os("MVG_RECORD").is_some() {
+        let transport = RecordingTransport::new(HttpsTransport::new(), FIXTURES);
+        MVG::builder().transport(transport).build()
+    } else {
+        fake_mvg()
+    }
+}
+
+#[tokio::test]
+async fn test_departures_from_transport() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    assert_eq!(departures.len(), 4);
+    assert_eq!(departures[0].label(), "U6");
+}
+
+#[tokio::test]
+async fn test_departure_details() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+
+    let u6 = &departures[0];
+    assert_eq!(u6.product(), Product::UBahn);
+    assert_eq!(u6.platform().map(|p| p.to_string()), Some("2".to_string()));
+    assert_eq!(u6.delay(), Some(chrono::Duration::minutes(2)));
+    assert_eq!(
+        u6.realtime_departure_time() - u6.departure_time(),
+        chrono::Duration::minutes(2)
+    );
+    assert!(u6.live() && !u6.cancelled() && !u6.sev());
+
+    let bus = &departures[2];
+    assert!(bus.cancelled());
+    assert_eq!(bus.platform(), None);
+
+    let sev = &departures[3];
+    assert!(sev.sev());
+    assert_eq!(sev.delay(), None);
+    assert_eq!(sev.realtime_departure_time(), sev.departure_time());
+}
+
+#[tokio::test]
+async fn test_serving_lines() {
+    let lines = fake_mvg().serving_lines(&station_id("de:09162:2")).await.unwrap();
+    assert_eq!(lines.len(), 2);
+    let u6 = &lines[0];
+    assert_eq!(u6.line_number(), "U6");
+    assert_eq!(u6.destination(), "Garching, Forschungszentrum");
+    assert_eq!(u6.product(), Product::UBahn);
+    assert_eq!(u6.partial_net(), "swm");
+    assert_eq!(u6.diva_id(), "010U6");
+    assert!(!u6.sev());
+    assert_eq!(lines[1].group(), LineGroup::SBahn);
+
+    // the bgw-pt API only knows the lines of the next departures
+    let lines = bgw_pt_mvg().serving_lines(&station_id("de:09162:2")).await.unwrap();
+    let labels: Vec<&str> = lines.iter().map(|l| l.line_number()).collect();
+    assert_eq!(labels, vec!["U6", "S8", "52", "Fähre", "210"]);
+    assert_eq!(lines[3].group(), LineGroup::Other);
+    assert_eq!(lines[4].group(), LineGroup::Bus);
+}
+
+#[test]
+fn test_line_groups() {
+    assert_eq!(LineGroup::of(Product::Bus, "N40"), LineGroup::NightBus);
+    assert_eq!(LineGroup::of(Product::Tram, "N19"), LineGroup::NightTram);
+    assert_eq!(LineGroup::of(Product::Tram, "19"), LineGroup::Tram);
+    assert_eq!(LineGroup::of(Product::Bahn, "RB6"), LineGroup::Other);
+    assert_eq!(LineGroup::NightBus.product(), Some(Product::Bus));
+    assert_eq!(LineGroup::NightTram.key(), "nachttram");
+
+    let mut lines = Lines::new();
+    for label in ["132", "52", "X30", "52"].iter() {
+        lines.add(LineGroup::Bus, label);
+    }
+    lines.add(LineGroup::UBahn, "U6");
+    lines.add(LineGroup::UBahn, "U3");
+    assert_eq!(lines.bus(), ["52", "132", "X30"]);
+    assert_eq!(lines.len(), 5);
+    let groups: Vec<LineGroup> = lines.groups().iter().map(|(g, _)| *g).collect();
+    assert_eq!(groups, vec![LineGroup::UBahn, LineGroup::Bus]);
+    assert_eq!(lines.group(LineGroup::UBahn), ["U3", "U6"]);
+}
+
+#[tokio::test]
+async fn test_departures_multi() {
+    let multi = fake_mvg()
+        .departures_multi(&[station_id("de:09162:2"), station_id("de:09162:15"), station_id("de:09162:999")])
+        .await;
+    assert!(!multi.is_complete());
+
+    let labels: Vec<_> = multi
+        .departures()
+        .iter()
+        .map(|(station, d)| format!("{} {}", station, d.label()))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "de:09162:15 19",
+            "de:09162:2 U6",
+            "de:09162:2 S8",
+            "de:09162:15 N19",
+            "de:09162:2 52",
+            "de:09162:2 SEV",
+        ]
+    );
+
+    assert_eq!(multi.errors().len(), 1);
+    let (station, error) = &multi.errors()[0];
+    assert_eq!(*station, "de:09162:999");
+    assert!(matches!(error, MVGError::NotFound { .. }));
+}
+
+/// 10:40 UTC on the day of the fixtures, the U6 leaves at 10:42 with 2 min delay
+fn fixture_clock() -> FixedClock {
+    FixedClock::new(Utc.ymd(2020, 11, 9).and_hms(10, 40, 0))
+}
+
+#[tokio::test]
+async fn test_relative_departure_times() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    let formatter = TimeFormatter::new(TimeFormat::Relative).clock(fixture_clock());
+    let times: Vec<_> = departures.iter().map(|d| formatter.departure(d)).collect();
+    assert_eq!(times, ["in 4 min", "in 5 min", "in 9 min", "in 12 min"]);
+
+    let late = TimeFormatter::new(TimeFormat::Relative)
+        .clock(FixedClock::new(Utc.ymd(2020, 11, 9).and_hms(10, 45, 30)));
+    assert_eq!(late.departure(&departures[0]), "now");
+    assert_eq!(late.departure(&departures[1]), "now");
+    assert_eq!(late.departure(&departures[2]), "in 3 min");
+}
+
+#[tokio::test]
+async fn test_mixed_departure_times() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    let formatter = TimeFormatter::new(TimeFormat::Mixed).clock(fixture_clock());
+    assert_eq!(formatter.departure(&departures[2]), "in 9 min");
+    // the SEV leaves 12 minutes from now
+    assert_eq!(
+        formatter.departure(&departures[3]),
+        formatter.absolute(&departures[3].departure_time())
+    );
+}
+
+#[test]
+fn test_absolute_times() {
+    let time = TIMEZONE.ymd(2020, 11, 9).and_hms(15, 7, 0);
+    let formatter = TimeFormatter::new(TimeFormat::Absolute);
+    assert_eq!(formatter.absolute(&time), "15:07");
+    assert_eq!(formatter.twelve_hour(true).absolute(&time), " 3:07 PM");
+
+    // in Munich's timezone, no matter where the times come from
+    let utc = Utc.ymd(2020, 7, 1).and_hms(15, 7, 0);
+    let formatter = TimeFormatter::new(TimeFormat::Absolute);
+    assert_eq!(formatter.absolute(&utc), "17:07");
+    let formatter = formatter.zone("UTC".parse().unwrap());
+    assert_eq!(formatter.absolute(&utc), "15:07");
+    assert_eq!(formatter.absolute_seconds(&utc), "15:07:00");
+    assert_eq!("Mixed".parse(), Ok(TimeFormat::Mixed));
+    assert!("soon".parse::<TimeFormat>().is_err());
+
+    assert_eq!("local".parse(), Ok(DisplayZone::Local));
+    assert_eq!(
+        "Europe/Berlin".parse::<DisplayZone>().map(|z| z.to_string()),
+        Ok(String::from("Europe/Berlin"))
+    );
+    assert!("Europe/Munich".parse::<DisplayZone>().is_err());
+}
+
+#[tokio::test]
+async fn test_times_in_munich() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    // 10:42 UTC, one hour ahead in November
+    let time = departures[0].departure_time();
+    assert_eq!(time.timezone(), TIMEZONE);
+    assert_eq!(time.format("%H:%M %Z").to_string(), "11:42 CET");
+
+    let connections = fake_mvg()
+        .connections(&station_id("de:09162:2"), &station_id("de:09184:460"))
+        .await
+        .unwrap();
+    assert_eq!(connections[0].departure_time().format("%H:%M").to_string(), "11:42");
+}
+
+#[test]
+fn test_format_delay() {
+    assert_eq!(format_delay(Some(chrono::Duration::minutes(3))), "+3");
+    assert_eq!(format_delay(Some(chrono::Duration::minutes(-1))), "-1");
+    assert_eq!(format_delay(Some(chrono::Duration::zero())), "");
+    assert_eq!(format_delay(None), "");
+}
+
+#[tokio::test]
+async fn test_departure_filter() {
+    let departures = fake_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+
+    let all = DepartureFilter::new().apply(departures);
+    assert_eq!(all.len(), 4);
+
+    let sbahn = DepartureFilter::new().product(Product::SBahn);
+    assert_eq!(all.iter().filter(|d| sbahn.matches(d)).count(), 2);
+
+    let lines = DepartureFilter::new().line("u6").line("52");
+    assert_eq!(all.iter().filter(|d| lines.matches(d)).count(), 2);
+
+    let to = DepartureFilter::new().destination("garching");
+    assert_eq!(all.iter().filter(|d| to.matches(d)).count(), 1);
+
+    let combined = DepartureFilter::new().product(Product::SBahn).platform("1");
+    let matching: Vec<_> = all.iter().filter(|d| combined.matches(d)).collect();
+    assert_eq!(matching.len(), 1);
+    assert_eq!(matching[0].label(), "S8");
+}
+
+#[tokio::test]
+async fn test_stations_from_transport() {
+    let stations = fake_mvg().stations_by_name("Marienplatz").await.unwrap();
+    assert_eq!(stations.len(), 2);
+}
+
+#[tokio::test]
+async fn test_stations_by_id() {
+    let id: StationId = "de:09162:2".parse().unwrap();
+    let stations = fake_mvg().stations_by_id(&id).await.unwrap();
+    assert!(matches!(&stations[0], Location::Station(s) if *s.id() == id && s.has_id(&id)));
+    assert_eq!(
+        query::query_url_id(BASE_URL, id.as_str()),
+        "http://localhost:8080/api/fahrinfo/location/query?q=de:09162:2"
+    );
+}
+
+#[test]
+fn test_station_id() {
+    let id: StationId = " DE:09184:460".parse().unwrap();
+    assert_eq!(id.as_str(), "de:09184:460");
+    assert!(id.is_global());
+    assert_eq!(id.diva_id(), Some(460));
+
+    let diva: StationId = "460".parse().unwrap();
+    assert!(!diva.is_global());
+    assert_eq!(diva.diva_id(), Some(460));
+
+    for invalid in &["", "Marienplatz", "de:09162", "de:09162:x", "de:09162:2:1", "d1:09162:2", "-2", "0", "000", "99999999999"] {
+        assert!(invalid.parse::<StationId>().is_err(), "{} was accepted", invalid);
+    }
+}
+
+#[tokio::test]
+async fn test_connections_from_transport() {
+    let connections = fake_mvg()
+        .connections(&station_id("de:09162:2"), &station_id("de:09184:460"))
+        .await
+        .unwrap();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].connection_parts().len(), 2);
+}
+
+#[tokio::test]
+async fn test_connection_details() {
+    let connections = fake_mvg()
+        .connections(&station_id("de:09162:2"), &station_id("de:09184:460"))
+        .await
+        .unwrap();
+    let con = &connections[0];
+    assert_eq!(con.duration(), chrono::Duration::minutes(34));
+    assert_eq!(con.changes(), 0);
+
+    let tp = con.transportations().next().unwrap();
+    assert_eq!(tp.delay(), chrono::Duration::minutes(1));
+    assert_eq!(tp.arrival_delay(), chrono::Duration::minutes(2));
+    assert_eq!(tp.departure_platform().and_then(|p| p.number()), Some(2));
+    assert_eq!(tp.arrival_platform().and_then(|p| p.number()), Some(1));
+    assert_eq!(tp.info_messages().len(), 1);
+    assert_eq!(tp.realtime_arrival_time() - tp.arrival_time(), chrono::Duration::minutes(2));
+
+    let stop = &tp.stops()[0];
+    assert!(matches!(stop.location(), Location::Station(s) if s.name() == "Odeonsplatz"));
+    assert_eq!(stop.delay(), chrono::Duration::zero());
+
+    assert_eq!(con.walking_time(), chrono::Duration::minutes(5));
+    assert_eq!(con.rings(), (1, 3));
+    assert!(!con.cancelled());
+
+    match &con.connection_parts()[1] {
+        ConnectionPart::Footway(fw) => assert_eq!(fw.duration(), chrono::Duration::minutes(5)),
+        other => panic!("expected a footway, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_changes_without_leaving_the_train() {
+    let fixture = std::fs::read_to_string(format!(
+        "{}/api_fahrinfo_routing_fromStation_de_09162_2_toStation_de_09184_460_88b979e2.json",
+        FIXTURES
+    ))
+    .unwrap();
+    // a second ride after the first one, e.g. a tram changing its number
+    let with_second_ride = |stay_seated: bool| {
+        let mut list: serde_json::Value = serde_json::from_str(&fixture).unwrap();
+        let parts = list["connectionList"][0]["connectionPartList"].as_array_mut().unwrap();
+        let mut ride = parts[0].clone();
+        ride["noChangingRequired"] = serde_json::Value::Bool(stay_seated);
+        parts.insert(1, ride);
+        let list: ConnectionList = serde_json::from_value(list).unwrap();
+        list.connection_list.into_iter().next().unwrap()
+    };
+    assert_eq!(with_second_ride(false).changes(), 1);
+    assert_eq!(with_second_ride(true).changes(), 0);
+}
+
+#[tokio::test]
+async fn test_stations_nearby_sorted() {
+    let nearby = fake_mvg().stations_nearby(48.1372, 11.5755).await.unwrap();
+    assert_eq!(nearby.len(), 3);
+    assert!(nearby.windows(2).all(|w| w[0].distance() <= w[1].distance()));
+    match nearby[0].location() {
+        Location::Station(s) => assert_eq!(s.id(), "de:09162:2"),
+        _ => panic!("expected a station"),
+    }
+}
+
+#[tokio::test]
+async fn test_interruptions() {
+    let interruptions = fake_mvg().interruptions().await.unwrap();
+    assert_eq!(interruptions.len(), 2);
+
+    let subway = &interruptions[0];
+    assert!(subway.affects_line("u3"));
+    assert!(subway.affects_product(Product::UBahn));
+    assert!(!subway.affects_product(Product::Tram));
+    assert!(subway.valid_until().is_some());
+    assert_eq!(
+        subway.plain_text(),
+        "Wegen Bauarbeiten fahren die Linien U3 und U6 nur alle 10 Minuten.\nBitte planen Sie mehr Zeit ein."
+    );
+
+    let tram = &interruptions[1];
+    assert!(tram.affects_line("N19"));
+    assert!(tram.valid_until().is_none());
+}
+
+fn marienplatz_locations() -> Vec<Location> {
+    let locations: Locations =
+        serde_json::from_str(include_str!("../fixtures/api_fahrinfo_location_queryWeb_q_Marienplatz_7fd93651.json")).unwrap();
+    locations.locations
+}
+
+#[test]
+fn test_resolve_ambiguous_station() {
+    match resolve_station("Marienplatz", marienplatz_locations()) {
+        Resolution::Ambiguous(candidates) => {
+            assert_eq!(candidates.len(), 2);
+            // the station in Munich is ranked first
+            assert_eq!(candidates[0].place(), "München");
+            assert_eq!(candidates[1].place(), "Oberalting");
+        }
+        other => panic!("expected ambiguous result, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_resolve_station_with_place() {
+    match resolve_station("marienplatz, oberalting", marienplatz_locations()) {
+        Resolution::Unique(station) => assert_eq!(station.id(), "de:09188:5516"),
+        other => panic!("expected unique result, got {:?}", other),
+    }
+    assert!(matches!(
+        resolve_station("Marienplatz, Augsburg", marienplatz_locations()),
+        Resolution::NotFound
+    ));
+}
+
+#[test]
+fn test_resolve_station_exact_name() {
+    let locations: Locations =
+        serde_json::from_str(include_str!("../fixtures/api_fahrinfo_location_nearby_latitude_48.1372_longitude_11.5755_d3a10f6b.json")).unwrap();
+    match resolve_station("Odeonsplatz", locations.locations) {
+        Resolution::Unique(station) => assert_eq!(station.id(), "de:09162:3"),
+        other => panic!("expected unique result, got {:?}", other),
+    }
+}
+
+/// Marienplatz in München and Oberalting, Odeonsplatz and Theatinerstraße
+fn index_locations() -> Vec<Location> {
+    let nearby: Locations = serde_json::from_str(include_str!(
+        "../fixtures/api_fahrinfo_location_nearby_latitude_48.1372_longitude_11.5755_d3a10f6b.json"
+    ))
+    .unwrap();
+    let mut locations = marienplatz_locations();
+    locations.extend(nearby.locations);
+    locations
+}
+
+fn unique_name(resolution: Option<Resolution>) -> String {
+    match resolution {
+        Some(Resolution::Unique(s)) => s.name(),
+        other => panic!("expected a unique station, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_fold() {
+    assert_eq!(fold("Theatinerstraße"), "theatinerstr");
+    assert_eq!(fold("Theatinerstrasse"), "theatinerstr");
+    assert_eq!(fold("THEATINERSTR."), "theatinerstr");
+    assert_eq!(fold("Sendlinger  Tor"), "sendlinger tor");
+    assert_eq!(fold("Garching-Hochbrück"), "garching hochbrueck");
+}
+
+#[test]
+fn test_fuzzy_station_search() {
+    let index = StationIndex::complete(&index_locations());
+    assert_eq!(index.len(), 4);
+
+    assert_eq!(unique_name(index.resolve("theatinerstr.")), "Theatinerstraße");
+    assert_eq!(unique_name(index.resolve("Theatinerstrasse")), "Theatinerstraße");
+    // typos
+    assert_eq!(unique_name(index.resolve("Odeonplatz")), "Odeonsplatz");
+    assert_eq!(unique_name(index.resolve("Odoensplatz")), "Odeonsplatz");
+    assert_eq!(unique_name(index.resolve("Marienplatz, Muenchen")), "Marienplatz");
+    assert!(matches!(index.resolve("Marienplatz"), Some(Resolution::Ambiguous(c)) if c.len() == 2));
+    // too many typos for a short name
+    assert!(index.resolve("Odn").is_none());
+    assert!(index.resolve("Hauptbahnhof").is_none());
+
+    let names: Vec<String> = index.search("platz").iter().map(|s| s.name()).collect();
+    assert_eq!(names, vec!["Marienplatz", "Odeonsplatz", "Marienplatz"]);
+}
+
+#[test]
+fn test_incomplete_index_only_knows_exact_names() {
+    let mut index = StationIndex::new();
+    assert!(index.merge(&index_locations()));
+    assert!(!index.merge(&index_locations()));
+    assert!(!index.is_complete());
+    assert_eq!(index.len(), 4);
+
+    assert_eq!(unique_name(index.resolve("Odeonsplatz, München")), "Odeonsplatz");
+    assert_eq!(unique_name(index.resolve("theatinerstrasse, muenchen")), "Theatinerstraße");
+    // the API might know better matches or stations of the same name elsewhere
+    assert!(index.resolve("Odeonsplatz").is_none());
+    assert!(index.resolve("Odeon, München").is_none());
+    assert!(index.resolve("Odeonplatz, München").is_none());
+    assert!(index.resolve("Marienplatz").is_none());
+    let odeonsplatz = |id: &str| index.by_id(&id.parse().unwrap()).map(|s| s.name());
+    assert_eq!(odeonsplatz("de:09162:3"), Some(String::from("Odeonsplatz")));
+    assert_eq!(odeonsplatz("3"), Some(String::from("Odeonsplatz")));
+    assert_eq!(odeonsplatz("de:09184:3"), None);
+}
+
+#[test]
+fn test_incomplete_index_with_one_station() {
+    let munich: Vec<Location> = marienplatz_locations()
+        .into_iter()
+        .filter(|l| matches!(l, Location::Station(s) if s.place() == "München"))
+        .collect();
+    let mut index = StationIndex::new();
+    assert!(index.merge(&munich));
+    assert_eq!(index.len(), 1);
+    // Marienplatz in Oberalting isn't known yet
+    assert!(index.resolve("Marienplatz").is_none());
+    assert_eq!(unique_name(index.resolve("Marienplatz, München")), "Marienplatz");
+    assert!(index.resolve("Marienplatz, Oberalting").is_none());
+}
+
+#[test]
+fn test_station_index_file() {
+    let path = std::env::temp_dir()
+        .join(format!("mvg_index_{}", std::process::id()))
+        .join("stations.json");
+    assert!(StationIndex::load(&path).unwrap().is_empty());
+
+    StationIndex::complete(&index_locations()).save(&path).unwrap();
+    let index = StationIndex::load(&path).unwrap();
+    assert!(index.is_complete());
+    assert_eq!(index.len(), 4);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_platform() {
+    let platform = Platform::from_api("Gleis 12").unwrap();
+    assert_eq!(platform.number(), Some(12));
+    assert!(platform.matches("12"));
+    assert!(platform.matches("gleis 12"));
+    assert!(!platform.matches("1"));
+    assert!(Platform::from_api(" ").is_none());
+}
+
+#[test]
+fn test_distance() {
+    // Marienplatz to Odeonsplatz is roughly 600 m
+    let d = distance(48.13725, 11.57542, 48.14258, 11.57732);
+    assert!((d - 608.0).abs() < 10.0, "distance was {}", d);
+}
+
+#[test]
+fn test_routing_options_url() {
+    let options = RoutingOptions::new()
+        .arrive_by(Utc.timestamp_millis(1604918400000))
+        .max_changes(2)
+        .max_walk_to_station(10)
+        .exclude(Product::Bus)
+        .exclude(Product::Tram);
+    assert_eq!(
+        query::routing_url(BASE_URL, "de:09162:2", "de:09184:460", &options),
+        "http://localhost:8080/api/fahrinfo/routing/?fromStation=de:09162:2&toStation=de:09184:460\
+         &time=1604918400000&arrival=true&maxTravelTimeFootwayToStation=10&changeLimit=2\
+         &transportTypeBus=false&transportTypeTram=false"
+    );
+}
+
+#[test]
+fn test_bgw_pt_routes_url() {
+    let options = RoutingOptions::new()
+        .depart_at(Utc.timestamp_millis(1604918400000))
+        .max_changes(2)
+        .exclude(Product::Bus);
+    assert_eq!(
+        query::bgw_pt_routes_url(BASE_URL, "de:09162:2", "de:09184:460", &options),
+        "http://localhost:8080/api/bgw-pt/v3/routes?originStationGlobalId=de:09162:2\
+         &destinationStationGlobalId=de:09184:460&routingDateTime=2020-11-09T10:40:00.000Z\
+         &routingDateTimeIsArrival=false&transportTypes=UBAHN,TRAM,SBAHN,BAHN"
+    );
+}
+
+#[test]
+fn test_routing_options_ignored_by_bgw_pt() {
+    assert!(RoutingOptions::new().exclude(Product::Bus).ignored_by_bgw_pt().is_empty());
+    let options = RoutingOptions::new().max_changes(1).max_walk_to_destination(5);
+    assert_eq!(
+        options.ignored_by_bgw_pt(),
+        vec!["maximum walking time to the destination", "maximum number of changes"]
+    );
+    assert_eq!(
+        options.bgw_pt_query_params(),
+        vec!["transportTypes=UBAHN,TRAM,SBAHN,BUS,REGIONAL_BUS,BAHN"]
+    );
+}
+
+#[test]
+fn test_routing_options_only() {
+    let options = RoutingOptions::new().only(&[Product::UBahn, Product::SBahn]);
+    assert!(options.allows(Product::UBahn));
+    assert!(!options.allows(Product::Bus));
+    assert!(!options.allows(Product::Tram));
+    assert!(!options.allows(Product::Bahn));
+    // fahrinfo can't exclude regional trains, bgw-pt can
+    assert_eq!(options.query_params().len(), 2);
+    assert!(options.bgw_pt_query_params()[0].ends_with("=UBAHN,SBAHN"));
+
+    let trains = RoutingOptions::new().only(&[Product::Bahn]);
+    assert_eq!(trains.query_params().len(), 4);
+    assert_eq!(trains.bgw_pt_query_params(), vec!["transportTypes=BAHN"]);
+}
+
+#[tokio::test]
+async fn test_error_status_from_transport() {
+    let result = fake_mvg().departures_by_id(&station_id("de:09162:9999")).await;
+    match result {
+        Err(MVGError::NotFound { url }) => {
+            assert_eq!(url, format!("{}/api/fahrinfo/departure/de:09162:9999?footway=0", BASE_URL))
+        }
+        other => panic!("expected NotFound, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_http_status_error() {
+    let body = "x".repeat(500);
+    let transport = FakeTransport::default().with("/.rest/betriebsaenderungen/api/interruptions", 503, &body);
+    let mvg = MVG::builder().base_url(BASE_URL).transport(transport).build();
+    let err = mvg.interruptions().await.unwrap_err();
+    match &err {
+        MVGError::HttpStatus { status, body, .. } => {
+            assert_eq!(*status, 503);
+            assert!(body.len() < 210);
+        }
+        other => panic!("expected HttpStatus, got {:?}", other),
+    }
+    assert!(err.to_string().starts_with("MVG API answered with HTTP 503 for http://localhost:8080/"));
+}
+
+#[tokio::test]
+async fn test_decode_error() {
+    let transport = FakeTransport::default().with("/.rest/betriebsaenderungen/api/interruptions", 200, "<html>");
+    let mvg = MVG::builder().base_url(BASE_URL).transport(transport).build();
+    let err = mvg.interruptions().await.unwrap_err();
+    assert!(matches!(err, MVGError::DecodeError { .. }));
+    assert!(std::error::Error::source(&err).is_some());
+    assert_eq!(err.url(), Some("http://localhost:8080/.rest/betriebsaenderungen/api/interruptions"));
+}
+
+/// Never answers in time
+struct SlowTransport;
+
+#[async_trait]
+impl Transport for SlowTransport {
+    async fn get(&self, _url: &str) -> Result<Response, MVGError> {
+        tokio::time::delay_for(Duration::from_secs(10)).await;
+        Ok(Response::new(200, ""))
+    }
+}
+
+#[tokio::test]
+async fn test_timeout() {
+    let mvg = MVG::builder()
+        .base_url(BASE_URL)
+        .transport(SlowTransport)
+        .timeout(Some(Duration::from_millis(10)))
+        .build();
+    let err = mvg.interruptions().await.unwrap_err();
+    assert!(matches!(err, MVGError::Timeout { .. }));
+}
+
+#[test]
+fn test_base_url_trailing_slash() {
+    let mvg = MVG::builder().base_url("http://localhost:8080/").build();
+    assert_eq!(mvg.base_url(), BASE_URL);
+}
+
+#[tokio::test]
+async fn test_bgw_pt_stations() {
+    let locations = bgw_pt_mvg().stations_by_name("Marienplatz").await.unwrap();
+    assert_eq!(locations.len(), 3);
+    match &locations[0] {
+        Location::Station(s) => {
+            assert_eq!(s.id(), "de:09162:2");
+            assert_eq!(s.products(), &vec![Product::UBahn, Product::Bus, Product::SBahn]);
+        }
+        other => panic!("expected a station, got {:?}", other),
+    }
+    assert!(matches!(&locations[1], Location::Address(a) if a.street() == "Marienplatz (Rathaus)"));
+    assert!(matches!(&locations[2], Location::Address(a) if a.street() == "Marienplatz 1"));
+
+    // it would only list a few stations
+    let err = bgw_pt_mvg().stations_by_name("").await.unwrap_err();
+    assert_eq!(err.to_string(), "listing all stations isn't supported by the bgw-pt API");
+}
+
+#[tokio::test]
+async fn test_bgw_pt_departures() {
+    let departures = bgw_pt_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    let labels: Vec<String> = departures.iter().map(|d| d.label()).collect();
+    assert_eq!(labels, vec!["U6", "S8", "52", "Fähre", "210"]);
+
+    let u6 = &departures[0];
+    assert_eq!(u6.product(), Product::UBahn);
+    assert_eq!(u6.delay(), Some(chrono::Duration::minutes(2)));
+    assert_eq!(u6.platform().and_then(|p| p.number()), Some(2));
+    assert_eq!(u6.departure_id(), "010U6:1604918520000");
+    assert_eq!(
+        u6.departure_time(),
+        TIMEZONE.ymd(2020, 11, 9).and_hms(11, 42, 0)
+    );
+    assert!(departures[2].cancelled());
+    // the library doesn't know ferries
+    assert_eq!(departures[3].product(), Product::Other);
+    assert_eq!(departures[4].product(), Product::Bus);
+    assert_eq!(departures[4].delay(), None);
+}
+
+#[tokio::test]
+async fn test_bgw_pt_connections() {
+    let connections = bgw_pt_mvg()
+        .connections(&station_id("de:09162:2"), &station_id("de:09184:460"))
+        .await
+        .unwrap();
+    assert_eq!(connections.len(), 2);
+    let con = &connections[0];
+    assert_eq!(con.departure_time(), TIMEZONE.ymd(2020, 11, 9).and_hms(11, 42, 0));
+    assert_eq!(con.duration(), chrono::Duration::minutes(37));
+    assert_eq!(con.walking_time(), chrono::Duration::minutes(6));
+    assert_eq!(con.rings(), (0, 1));
+    assert!(matches!(con.to(), Location::Location(_)));
+
+    let tp = con.transportations().next().unwrap();
+    assert_eq!(tp.label(), "U6");
+    assert_eq!(tp.delay(), chrono::Duration::minutes(2));
+    assert_eq!(tp.arrival_delay(), chrono::Duration::minutes(1));
+    assert_eq!(tp.arrival_platform().and_then(|p| p.number()), Some(1));
+    assert!(matches!(tp.stops()[0].location(), Location::Station(s) if s.name() == "Odeonsplatz"));
+
+    // the library doesn't know ferries
+    let ferry = connections[1].transportations().next().unwrap();
+    assert_eq!(ferry.label(), "Fähre");
+    assert_eq!(*ferry.product(), Product::Other);
+}
+
+#[tokio::test]
+async fn test_connections_without_products() {
+    let options = RoutingOptions::new().only(&[]);
+    assert!(!options.allows_any());
+    assert!(RoutingOptions::new().only(&[Product::Bahn]).allows_any());
+    let result = bgw_pt_mvg()
+        .connections_with(&station_id("de:09162:2"), &station_id("de:09184:460"), &options)
+        .await;
+    assert!(matches!(result, Err(MVGError::ArgumentError(_))));
+}
+
+#[tokio::test]
+async fn test_bgw_pt_nearby() {
+    let nearby = bgw_pt_mvg().stations_nearby(48.1372, 11.5755).await.unwrap();
+    let names: Vec<String> = nearby
+        .iter()
+        .filter_map(|n| match n.location() {
+            Location::Station(s) => Some(s.name()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, vec!["Marienplatz", "Theatinerstraße", "Odeonsplatz"]);
+}
+
+#[tokio::test]
+async fn test_backend_fallback() {
+    let transport = FakeTransport::default()
+        .with("/api/fahrinfo/location/queryWeb?q=Garching", 503, "maintenance")
+        .with(
+            "/api/bgw-pt/v3/locations?query=Garching&locationTypes=STATION,ADDRESS,POI",
+            200,
+            "[]",
+        );
+    let requests = transport.requests.clone();
+    let mvg = MVG::builder().base_url(BASE_URL).transport(transport).build();
+    assert_eq!(mvg.active_backend(), Backend::Fahrinfo);
+
+    assert!(mvg.stations_by_name("Garching").await.unwrap().is_empty());
+    assert_eq!(mvg.active_backend(), Backend::BgwPt);
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // stays with the backend which answered
+    assert!(mvg.stations_by_name("Garching").await.unwrap().is_empty());
+    assert_eq!(requests.lock().unwrap().len(), 3);
+
+    // the error of the active backend if both fail
+    match mvg.stations_by_name("Nowhere").await {
+        Err(MVGError::NotFound { url }) => assert!(url.contains("/bgw-pt/"), "{}", url),
+        other => panic!("expected NotFound, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_backend_fallback_only_if_unavailable() {
+    let transport = FakeTransport::default()
+        .with("/api/fahrinfo/location/queryWeb?q=Garching", 200, "<html>")
+        .with(
+            "/api/bgw-pt/v3/locations?query=Garching&locationTypes=STATION,ADDRESS,POI",
+            200,
+            "[]",
+        );
+    let requests = transport.requests.clone();
+    let mvg = MVG::builder().base_url(BASE_URL).transport(transport).build();
+    // a broken response isn't an outage
+    assert!(matches!(
+        mvg.stations_by_name("Garching").await,
+        Err(MVGError::DecodeError { .. })
+    ));
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    assert_eq!(mvg.active_backend(), Backend::Fahrinfo);
+}
+
+#[tokio::test]
+async fn test_ignored_routing_options_after_fallback() {
+    let transport = FakeTransport::default()
+        .with(
+            "/api/fahrinfo/routing/?fromStation=de:09162:2&toStation=de:09184:460&changeLimit=1",
+            503,
+            "maintenance",
+        )
+        .with(
+            "/api/bgw-pt/v3/routes?originStationGlobalId=de:09162:2&destinationStationGlobalId=de:09184:460\
+             &transportTypes=UBAHN,TRAM,SBAHN,BUS,REGIONAL_BUS,BAHN",
+            200,
+            "[]",
+        );
+    let mvg = MVG::builder().base_url(BASE_URL).transport(transport).build();
+    let options = RoutingOptions::new().max_changes(1);
+    assert!(mvg.ignored_routing_options(&options).is_empty());
+
+    let connections = mvg
+        .connections_with(&station_id("de:09162:2"), &station_id("de:09184:460"), &options)
+        .await;
+    assert!(connections.unwrap().is_empty());
+    assert_eq!(mvg.ignored_routing_options(&options), vec!["maximum number of changes"]);
+    let provider: &dyn TransitProvider = &mvg;
+    assert_eq!(provider.ignored_routing_options(&options).len(), 1);
+    assert!(TinyProvider.ignored_routing_options(&options).is_empty());
+}
+
+#[tokio::test]
+async fn test_fixed_backend_doesnt_fall_back() {
+    let transport = FakeTransport::default()
+        .with("/api/fahrinfo/location/queryWeb?q=Garching", 503, "maintenance")
+        .with(
+            "/api/bgw-pt/v3/locations?query=Garching&locationTypes=STATION,ADDRESS,POI",
+            200,
+            "[]",
+        );
+    let requests = transport.requests.clone();
+    let mvg = MVG::builder()
+        .base_url(BASE_URL)
+        .transport(transport)
+        .backend(Backend::Fahrinfo)
+        .build();
+    assert!(matches!(
+        mvg.stations_by_name("Garching").await,
+        Err(MVGError::HttpStatus { status: 503, .. })
+    ));
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    assert_eq!("bgw-pt".parse(), Ok(Backend::BgwPt));
+    assert_eq!(Backend::BgwPt.to_string(), "bgw-pt");
+}
+
+/// A network which only knows Marienplatz and has no departures
+struct TinyProvider;
+
+#[async_trait]
+impl TransitProvider for TinyProvider {
+    fn name(&self) -> &'static str {
+        "tiny"
+    }
+
+    async fn stations_by_name(&self, search: &str) -> Result<Vec<Location>, MVGError> {
+        fake_mvg().stations_by_name(search).await
+    }
+
+    async fn stations_by_id(&self, _id: &StationId) -> Result<Vec<Location>, MVGError> {
+        Ok(Vec::new())
+    }
+
+    async fn departures_by_id(&self, _station_id: &StationId) -> Result<Vec<Departure>, MVGError> {
+        Ok(Vec::new())
+    }
+
+    async fn connections_with(
+        &self,
+        _from_id: &StationId,
+        _to_id: &StationId,
+        _options: &RoutingOptions,
+    ) -> Result<Vec<Connection>, MVGError> {
+        Ok(Vec::new())
+    }
+}
+
+#[tokio::test]
+async fn test_provider_defaults() {
+    let provider: &dyn TransitProvider = &TinyProvider;
+    assert!(matches!(
+        provider.resolve_station("Marienplatz").await,
+        Ok(Resolution::Ambiguous(candidates)) if candidates.len() == 2
+    ));
+    assert!(provider.departures_multi(&[station_id("1")]).await.is_complete());
+
+    let err = provider.interruptions().await.unwrap_err();
+    assert!(matches!(err, MVGError::Unsupported { provider: "tiny", .. }));
+    assert_eq!(err.to_string(), "listing interruptions isn't supported by tiny");
+}
+
+#[tokio::test]
+async fn test_mvg_as_provider() {
+    let mvg = fake_mvg();
+    let provider: &dyn TransitProvider = &mvg;
+    assert_eq!(provider.name(), "mvg");
+    let ids = vec![station_id("de:09162:2"), station_id("de:09162:15")];
+    assert_eq!(provider.departures_multi(&ids).await.departures().len(), 6);
+    assert_eq!(provider.interruptions().await.unwrap().len(), 2);
+    assert_eq!("MVG".parse(), Ok(Provider::Mvg));
+}
+
+// The following tests answer from the recorded fixtures. Run them with
+// `MVG_RECORD=1 cargo test test_retrieve` to record them from the live API.
 
 #[tokio::test]
 async fn test_retrieve_departures() {
-    let mvg = MVG::new();
-    let _departures = mvg.departures_by_id(&"de:09162:2").await.unwrap();
+    let departures = recorded_mvg().departures_by_id(&station_id("de:09162:2")).await.unwrap();
+    assert!(!departures.is_empty());
 }
 
 #[tokio::test]
 async fn test_retrieve_stations() {
-    let mvg = MVG::new();
-    let _stations = mvg.stations_by_name(&"Marienplatz").await.unwrap();
+    let stations = recorded_mvg().stations_by_name("Marienplatz").await.unwrap();
+    assert!(!stations.is_empty());
 }
 
 #[tokio::test]
 async fn test_retrieve_connections() {
-    let mvg = MVG::new();
-    let from_id = "de:09162:2";
-    let to_id = "de:09162:1622";
-    let _connections = mvg.connections(from_id, to_id);
+    let from_id = station_id("de:09162:2");
+    let to_id = station_id("de:09184:460");
+    let connections = recorded_mvg().connections(&from_id, &to_id).await.unwrap();
+    assert!(!connections.is_empty());
+}
+
+#[tokio::test]
+async fn test_retrieve_nearby() {
+    let nearby = recorded_mvg().stations_nearby(48.1372, 11.5755).await.unwrap();
+    assert!(!nearby.is_empty());
+}
+
+#[tokio::test]
+async fn test_retrieve_interruptions() {
+    let interruptions = recorded_mvg().interruptions().await.unwrap();
+    assert!(!interruptions.is_empty());
+}
+
+#[test]
+fn test_fixture_name() {
+    assert_eq!(
+        fixture_name("https://www.mvg.de/api/fahrinfo/departure/de:09162:2?footway=0"),
+        "api_fahrinfo_departure_de_09162_2_footway_0_38ae4402.json"
+    );
+    assert_eq!(
+        fixture_name("http://localhost:8080/.rest/betriebsaenderungen/api/interruptions"),
+        "rest_betriebsaenderungen_api_interruptions_f5da1075.json"
+    );
+    assert!(fixture_name("https://www.mvg.de").starts_with("index_"));
+    // the host doesn't matter
+    assert_eq!(
+        fixture_name("https://www.mvg.de/api/fahrinfo/location/queryWeb?q=Garching"),
+        fixture_name("http://localhost:8080/api/fahrinfo/location/queryWeb?q=Garching")
+    );
+    // both used to be api_fahrinfo_location_queryWeb_q_Garching_Forschungszentrum.json
+    let with_comma = fixture_name("/api/fahrinfo/location/queryWeb?q=Garching,Forschungszentrum");
+    let with_slash = fixture_name("/api/fahrinfo/location/queryWeb?q=Garching/Forschungszentrum");
+    assert_ne!(with_comma, with_slash);
+    assert!(with_comma.starts_with("api_fahrinfo_location_queryWeb_q_Garching_Forschungszentrum_"));
+    assert!(with_slash.starts_with("api_fahrinfo_location_queryWeb_q_Garching_Forschungszentrum_"));
+
+    let long = fixture_name(&format!("/api/fahrinfo/location/queryWeb?q={}", "a".repeat(300)));
+    assert!(long.len() <= 255);
+    assert_ne!(
+        long,
+        fixture_name(&format!("/api/fahrinfo/location/queryWeb?q={}", "a".repeat(301)))
+    );
+}
+
+#[tokio::test]
+async fn test_record_and_replay() {
+    let dir = std::env::temp_dir().join(format!("mvg_fixtures_{}", std::process::id()));
+    let live = FakeTransport::default()
+        .with("/api/fahrinfo/location/queryWeb?q=Garching", 200, r#"{"locations":[]}"#)
+        .with("/api/fahrinfo/location/queryWeb?q=Nowhere", 500, "oops");
+    let recording = MVG::builder()
+        .base_url(BASE_URL)
+        .transport(RecordingTransport::new(live, &dir))
+        .build();
+    assert!(recording.stations_by_name("Garching").await.unwrap().is_empty());
+    assert!(recording.stations_by_name("Nowhere").await.is_err());
+
+    let replay = MVG::builder()
+        .base_url("https://www.mvg.de")
+        .transport(ReplayTransport::new(&dir))
+        .build();
+    assert!(replay.stations_by_name("Garching").await.unwrap().is_empty());
+    // failed requests aren't recorded
+    assert!(matches!(
+        replay.stations_by_name("Nowhere").await,
+        Err(MVGError::NotFound { .. })
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
 #[test]
@@ -57,3 +1077,31 @@ fn test_into_location() {
       }";
     let _location: Location = serde_json::from_str(json).unwrap();
 }
+
+#[test]
+fn test_station_lines() {
+    let station = |lines: &str| {
+        let json = format!(
+            "{{\"type\": \"station\", \"latitude\": 48.137, \"longitude\": 11.575,
+                \"id\": \"de:09162:2\", \"divaId\": 2, \"place\": \"München\",
+                \"name\": \"Marienplatz\", \"hasLiveData\": true, \"hasZoomData\": true,
+                \"products\": [\"UBAHN\"], \"aliases\": null, \"tariffZones\": \"m\"{}}}",
+            lines
+        );
+        match serde_json::from_str(&json).unwrap() {
+            Location::Station(s) => s,
+            other => panic!("expected a station, got {:?}", other),
+        }
+    };
+    let marienplatz = station(
+        ", \"lines\": {\"ubahn\": [\"U3\", \"U6\"], \"nachtbus\": [\"N40\"], \"tram\": []}",
+    );
+    let lines = marienplatz.lines();
+    assert_eq!(lines.ubahn(), ["U3", "U6"]);
+    assert_eq!(lines.night_bus(), ["N40"]);
+    assert!(lines.tram().is_empty() && lines.other().is_empty());
+    assert_eq!(lines.len(), 3);
+
+    // stations converted from the bgw-pt API have no lines
+    assert!(station("").lines().is_empty());
+}
@@ -0,0 +1,289 @@
+//! Formatting of departure times, absolute (`10:42`) or relative to the
+//! current time (`in 4 min`).
+
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::data::departure::Departure;
+use crate::TIMEZONE;
+
+/// Source of the current time, replaceable to make relative times testable
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock which always shows the same time
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(DateTime<Utc>);
+
+impl FixedClock {
+    pub fn new<Z: TimeZone>(time: DateTime<Z>) -> Self {
+        FixedClock(time.with_timezone(&Utc))
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// How times are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeFormat {
+    /// the time of day, e.g. `10:42`
+    #[default]
+    Absolute,
+    /// minutes from now, e.g. `in 4 min` or `now`
+    Relative,
+    /// relative for the next [`MIXED_RELATIVE_MINUTES`] minutes, absolute after
+    Mixed,
+}
+
+/// Minutes ahead for which [`TimeFormat::Mixed`] shows relative times
+pub const MIXED_RELATIVE_MINUTES: i64 = 10;
+
+impl std::str::FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "absolute" => Ok(TimeFormat::Absolute),
+            "relative" => Ok(TimeFormat::Relative),
+            "mixed" => Ok(TimeFormat::Mixed),
+            _ => Err(format!(
+                "Unknown time format: {} (expected absolute, relative or mixed)",
+                s
+            )),
+        }
+    }
+}
+
+/// Timezone in which times are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayZone {
+    /// a zone of the tz database, by default Munich's
+    Named(Tz),
+    /// the zone of the system
+    Local,
+}
+
+impl Default for DisplayZone {
+    fn default() -> Self {
+        DisplayZone::Named(TIMEZONE)
+    }
+}
+
+impl std::str::FromStr for DisplayZone {
+    type Err = String;
+
+    /// `local` or a name of the tz database like `Europe/Berlin`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(DisplayZone::Local);
+        }
+        s.parse()
+            .map(DisplayZone::Named)
+            .map_err(|_| format!("Unknown timezone: {} (expected local or e.g. Europe/Berlin)", s))
+    }
+}
+
+impl std::fmt::Display for DisplayZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayZone::Named(tz) => write!(f, "{}", tz.name()),
+            DisplayZone::Local => f.write_str("local"),
+        }
+    }
+}
+
+impl Serialize for DisplayZone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DisplayZone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl DisplayZone {
+    /// Format `time` in this zone with a strftime like `format`
+    pub fn format<Z: TimeZone>(&self, time: &DateTime<Z>, format: &str) -> String {
+        match self {
+            DisplayZone::Named(tz) => time.with_timezone(tz).format(format).to_string(),
+            DisplayZone::Local => time.with_timezone(&Local).format(format).to_string(),
+        }
+    }
+
+    /// The date in this zone at `time`
+    pub fn date_of<Z: TimeZone>(&self, time: &DateTime<Z>) -> NaiveDate {
+        match self {
+            DisplayZone::Named(tz) => time.with_timezone(tz).date().naive_local(),
+            DisplayZone::Local => time.with_timezone(&Local).date().naive_local(),
+        }
+    }
+
+    /// `naive` as a time of this zone, the earlier one if the clocks are
+    /// turned back. `None` if the clocks skip it.
+    pub fn from_naive(&self, naive: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            DisplayZone::Named(tz) => tz
+                .from_local_datetime(naive)
+                .earliest()
+                .map(|t| t.with_timezone(&t.offset().fix())),
+            DisplayZone::Local => Local
+                .from_local_datetime(naive)
+                .earliest()
+                .map(|t| t.with_timezone(&t.offset().fix())),
+        }
+    }
+}
+
+/// Formats times in a [`TimeFormat`] with 24 or 12 hour clock times.
+///
+/// ```
+/// use chrono::TimeZone;
+/// use mvg_lib::time_format::{FixedClock, TimeFormat, TimeFormatter};
+/// use mvg_lib::TIMEZONE;
+///
+/// let now = TIMEZONE.ymd(2020, 11, 9).and_hms(10, 38, 0);
+/// let formatter = TimeFormatter::new(TimeFormat::Relative).clock(FixedClock::new(now));
+/// assert_eq!(formatter.format(now + chrono::Duration::minutes(4), None), "in 4 min");
+/// ```
+pub struct TimeFormatter {
+    format: TimeFormat,
+    twelve_hour: bool,
+    zone: DisplayZone,
+    clock: Box<dyn Clock>,
+}
+
+impl TimeFormatter {
+    /// Formatter with 24 hour times in Munich using the system's clock
+    pub fn new(format: TimeFormat) -> Self {
+        TimeFormatter {
+            format,
+            twelve_hour: false,
+            zone: DisplayZone::default(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Show absolute times like `10:42 AM`
+    pub fn twelve_hour(mut self, twelve_hour: bool) -> Self {
+        self.twelve_hour = twelve_hour;
+        self
+    }
+
+    /// Show absolute times in `zone` instead of Munich's timezone
+    pub fn zone(mut self, zone: DisplayZone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// Use `clock` instead of the system's clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Whole minutes from now until `time`, negative for past times
+    pub fn minutes_until<Z: TimeZone>(&self, time: &DateTime<Z>) -> i64 {
+        (time.with_timezone(&Utc) - self.now())
+            .num_seconds()
+            .div_euclid(60)
+    }
+
+    /// A scheduled time with its delay.
+    ///
+    /// Absolute times are the scheduled ones since the delay is usually
+    /// shown next to them, relative times include the delay.
+    pub fn format<Z: TimeZone>(&self, scheduled: DateTime<Z>, delay: Option<Duration>) -> String {
+        let actual = scheduled.clone() + delay.unwrap_or_else(Duration::zero);
+        let relative = match self.format {
+            TimeFormat::Absolute => false,
+            TimeFormat::Relative => true,
+            TimeFormat::Mixed => self.minutes_until(&actual) < MIXED_RELATIVE_MINUTES,
+        };
+        if relative {
+            self.relative(&actual)
+        } else {
+            self.absolute(&scheduled)
+        }
+    }
+
+    pub fn departure(&self, departure: &Departure) -> String {
+        self.format(departure.departure_time(), departure.delay())
+    }
+
+    /// The time of day, e.g. `10:42` or `10:42 AM`
+    pub fn absolute<Z: TimeZone>(&self, time: &DateTime<Z>) -> String {
+        if self.twelve_hour {
+            self.zone.format(time, "%_I:%M %p")
+        } else {
+            self.zone.format(time, "%_H:%M")
+        }
+    }
+
+    /// The time of day with seconds, e.g. `10:42:07` or `10:42:07 AM`
+    pub fn absolute_seconds<Z: TimeZone>(&self, time: &DateTime<Z>) -> String {
+        if self.twelve_hour {
+            self.zone.format(time, "%_I:%M:%S %p")
+        } else {
+            self.zone.format(time, "%_H:%M:%S")
+        }
+    }
+
+    /// The time from now, e.g. `in 4 min`, or `now` for current and past times
+    pub fn relative<Z: TimeZone>(&self, time: &DateTime<Z>) -> String {
+        match self.minutes_until(time) {
+            minutes if minutes <= 0 => String::from("now"),
+            minutes => format!("in {} min", minutes),
+        }
+    }
+
+    /// Characters needed by most formatted times, to align them in columns
+    pub fn width(&self) -> usize {
+        match (self.format, self.twelve_hour) {
+            (TimeFormat::Absolute, false) => 5,
+            (TimeFormat::Absolute, true) => 8,
+            _ => 9,
+        }
+    }
+}
+
+impl Default for TimeFormatter {
+    fn default() -> Self {
+        TimeFormatter::new(TimeFormat::default())
+    }
+}
+
+/// A delay in minutes like `+3`, empty without a delay
+pub fn format_delay(delay: Option<Duration>) -> String {
+    match delay.map(|d| d.num_minutes()) {
+        Some(minutes) if minutes > 0 => format!("+{}", minutes),
+        Some(minutes) if minutes < 0 => minutes.to_string(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use hyper::{client::HttpConnector, Client};
+use hyper_tls::HttpsConnector;
+
+use crate::data::MVGError;
+
+/// Raw answer of a transport: HTTP status code and the response body.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+/// Performs the HTTP requests for [`MVG`](crate::MVG).
+///
+/// The default is [`HttpsTransport`], which talks to the network. Tests
+/// and local setups can provide their own implementation, e.g. one that
+/// answers from memory.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Fetch `url` via GET.
+    async fn get(&self, url: &str) -> Result<Response, MVGError>;
+}
+
+/// Transport using a hyper client with TLS support.
+pub struct HttpsTransport {
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl HttpsTransport {
+    pub fn new() -> Self {
+        let https = HttpsConnector::new();
+        let client = Client::builder().build::<_, hyper::Body>(https);
+        HttpsTransport { client }
+    }
+}
+
+impl Default for HttpsTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Transport for HttpsTransport {
+    async fn get(&self, url: &str) -> Result<Response, MVGError> {
+        let url = url.parse::<hyper::Uri>()?;
+
+        let res = self.client.get(url).await?;
+        let status = res.status().as_u16();
+
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        Ok(Response::new(status, String::from_utf8_lossy(&body)))
+    }
+}
+
+/// Serves responses recorded by [`RecordingTransport`] from a directory.
+///
+/// Requests without a fixture are answered with 404, like unknown URLs of
+/// the API. The host of the requested URL is ignored, so fixtures recorded
+/// from the MVG work with any base URL.
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        ReplayTransport {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn get(&self, url: &str) -> Result<Response, MVGError> {
+        let path = self.dir.join(fixture_name(url));
+        match std::fs::read_to_string(&path) {
+            Ok(body) => Ok(Response::new(200, body)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Response::new(404, "")),
+            Err(source) => Err(MVGError::Fixture { path, source }),
+        }
+    }
+}
+
+/// Fetches responses with another transport and saves the successful ones
+/// as fixtures for [`ReplayTransport`].
+///
+/// ```no_run
+/// use mvg_lib::transport::{HttpsTransport, RecordingTransport};
+/// use mvg_lib::MVG;
+///
+/// let recording = RecordingTransport::new(HttpsTransport::new(), "fixtures");
+/// let mvg = MVG::builder().transport(recording).build();
+/// ```
+pub struct RecordingTransport<T: Transport> {
+    inner: T,
+    dir: PathBuf,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new<P: AsRef<Path>>(inner: T, dir: P) -> Self {
+        RecordingTransport {
+            inner,
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn get(&self, url: &str) -> Result<Response, MVGError> {
+        let response = self.inner.get(url).await?;
+        if response.status != 200 {
+            return Ok(response);
+        }
+        // pretty JSON keeps changes of fixtures reviewable
+        let body = serde_json::from_str::<serde_json::Value>(&response.body)
+            .and_then(|json| serde_json::to_string_pretty(&json))
+            .unwrap_or_else(|_| response.body.clone());
+        let path = self.dir.join(fixture_name(url));
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&path, body + "\n"))
+            .map_err(|source| MVGError::Fixture { path, source })?;
+        Ok(response)
+    }
+}
+
+/// File name of the fixture for `url`, made of its path and query.
+///
+/// `https://www.mvg.de/api/fahrinfo/departure/de:09162:2?footway=0`
+/// is stored as `api_fahrinfo_departure_de_09162_2_footway_0_38ae4402.json`.
+/// The readable part drops characters that can't be in file names and is cut
+/// to [`FIXTURE_NAME_MAX_CHARS`], the hash of the path and query at the end
+/// keeps the names of different URLs apart.
+pub fn fixture_name(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = without_scheme
+        .find('/')
+        .map_or("", |i| &without_scheme[i..]);
+    let mut name = String::new();
+    for c in path.chars() {
+        // no leading dots, the fixture shouldn't be hidden
+        if c.is_ascii_alphanumeric() || c == '-' || (c == '.' && !name.is_empty()) {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    // only ASCII is left, cutting can't split a character
+    name.truncate(FIXTURE_NAME_MAX_CHARS);
+    let name = name.trim_end_matches('_');
+    format!(
+        "{}_{:08x}.json",
+        if name.is_empty() { "index" } else { name },
+        fnv1a(path.as_bytes())
+    )
+}
+
+/// Length of the readable part of fixture names, file names of most file
+/// systems can't be longer than 255 bytes
+pub const FIXTURE_NAME_MAX_CHARS: usize = 200;
+
+/// 32 bit FNV-1a hash, unlike the hasher of std it's the same everywhere
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
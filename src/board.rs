@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use mvg_lib::data::departure::Departure;
+use mvg_lib::data::location::Station;
+use mvg_lib::data::station_id::StationId;
+
+use crate::{fit, format_departure, PAINTER, STATION_NAME_MAX_CHARS};
+
+/// The stations of a departure board and the minutes it takes to walk there
+pub struct Board {
+    stations: Vec<Station>,
+    walk_minutes: HashMap<StationId, u32>,
+}
+
+impl Board {
+    /// `walk_minutes` is keyed by station id, stations without an entry are
+    /// assumed to be reachable at any time
+    pub fn new(stations: Vec<Station>, walk_minutes: HashMap<StationId, u32>) -> Self {
+        Board {
+            stations,
+            walk_minutes,
+        }
+    }
+
+    pub fn stations(&self) -> &[Station] {
+        &self.stations
+    }
+
+    pub fn station_ids(&self) -> Vec<StationId> {
+        self.stations.iter().map(|s| s.id().clone()).collect()
+    }
+
+    /// "Departures at station …" for one or several stations
+    pub fn title(&self) -> String {
+        match self.stations.as_slice() {
+            [station] => format!("Departures at station {}, {}", station.name(), station.place()),
+            stations => {
+                let names: Vec<String> = stations.iter().map(|s| s.name()).collect();
+                format!("Departures at stations {}", names.join(", "))
+            }
+        }
+    }
+
+    /// Name of the station with id `station_id`
+    pub fn station_name(&self, station_id: &StationId) -> String {
+        self.stations
+            .iter()
+            .find(|s| s.id() == station_id)
+            .map(|s| s.name())
+            .unwrap_or_else(|| station_id.to_string())
+    }
+
+    /// Minutes left at `now` until one has to leave to catch `dep`, negative
+    /// if it's too late. `None` without a walking time for the station.
+    pub fn leave_in(&self, station_id: &StationId, dep: &Departure, now: DateTime<Utc>) -> Option<i64> {
+        let walk = *self.walk_minutes.get(station_id)?;
+        let leave_at = dep.realtime_departure_time() - chrono::Duration::minutes(walk as i64);
+        // rounded down, half a minute too late is too late
+        Some((leave_at.with_timezone(&Utc) - now).num_seconds().div_euclid(60))
+    }
+
+    /// whether `dep` can still be caught when leaving at `now`
+    pub fn reachable(&self, station_id: &StationId, dep: &Departure, now: DateTime<Utc>) -> bool {
+        self.leave_in(station_id, dep, now).is_none_or(|minutes| minutes >= 0)
+    }
+
+    /// "leave in 3 min", "leave now" or "too late", `None` without a walking
+    /// time for the station
+    pub fn leave_note(&self, station_id: &StationId, dep: &Departure, now: DateTime<Utc>) -> Option<String> {
+        self.leave_in(station_id, dep, now).map(|minutes| match minutes {
+            m if m < 0 => String::from("too late"),
+            0 => String::from("leave now"),
+            m => format!("leave in {} min", m),
+        })
+    }
+
+    /// A departure of the board.
+    ///
+    /// Boards of several stations have a station column, boards with walking
+    /// times a column telling when to leave. Departures which can't be
+    /// reached anymore are marked "too late" and greyed out.
+    pub fn line(&self, station_id: &StationId, dep: &Departure, highlight_delay: bool, now: DateTime<Utc>) -> String {
+        let mut line = String::new();
+        if self.stations.len() > 1 {
+            let width = self
+                .stations
+                .iter()
+                .map(|s| s.name().chars().count())
+                .max()
+                .unwrap_or(0)
+                .min(STATION_NAME_MAX_CHARS);
+            line.push_str(&fit(self.station_name(station_id), width));
+            line.push(' ');
+        }
+        line.push_str(&format_departure(dep, highlight_delay));
+        if self.walk_minutes.is_empty() {
+            return line;
+        }
+        if let Some(note) = self.leave_note(station_id, dep, now) {
+            line.push('\t');
+            line.push_str(&note);
+        }
+        if self.reachable(station_id, dep, now) {
+            line
+        } else {
+            PAINTER.faint(&line)
+        }
+    }
+}
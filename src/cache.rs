@@ -0,0 +1,44 @@
+//! The station index kept between runs, so station names can be resolved
+//! without asking the API.
+
+use std::path::{Path, PathBuf};
+
+use mvg_lib::data::location::Location;
+use mvg_lib::provider::Provider;
+use mvg_lib::station_index::StationIndex;
+
+/// Location of the station index of `provider`,
+/// `$XDG_CACHE_HOME/mvg/stations-<provider>.json`
+pub fn index_location(provider: Provider) -> PathBuf {
+    locate_index(provider, |var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+/// [`index_location`] with the environment passed in
+pub fn locate_index<E>(provider: Provider, env: E) -> PathBuf
+where
+    E: Fn(&str) -> Option<String>,
+{
+    // relative paths in XDG_CACHE_HOME are invalid and have to be ignored
+    let cache_home = env("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| env("HOME").map(PathBuf::from).unwrap_or_default().join(".cache"));
+    cache_home
+        .join("mvg")
+        .join(format!("stations-{}.json", provider))
+}
+
+/// The index at `path`. An unreadable index is an empty one, the API still
+/// knows all stations.
+pub fn load(path: &Path) -> StationIndex {
+    StationIndex::load(path).unwrap_or_default()
+}
+
+/// Add the stations among `locations` to `index`, writing it to `path` if
+/// that taught it anything
+pub fn remember(index: &mut StationIndex, path: &Path, locations: &[Location]) {
+    if index.merge(locations) {
+        // without a writable cache, names are just resolved by the API
+        let _ = index.save(path);
+    }
+}
@@ -1,41 +1,260 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use mvg_lib::backend::Backend;
+use mvg_lib::provider::Provider;
+use mvg_lib::time_format::{DisplayZone, TimeFormat};
 use serde::{Deserialize, Serialize};
 
-use lazy_static::lazy_static;
+/// Environment variable overriding the location of the config file
+pub const CONFIG_ENV: &str = "MVG_CONFIG";
 
-lazy_static! {
-    pub static ref DEFAULT_LOCATION: String = {
-        let mut home_dir =
-            std::path::PathBuf::from(std::env::var("HOME").unwrap_or(String::from("")));
-        home_dir.push(".mvg.conf");
-        String::from(home_dir.to_str().unwrap())
-    };
+/// Location of the config file.
+///
+/// In order of precedence: the `--config` flag, the `MVG_CONFIG` environment
+/// variable and `$XDG_CONFIG_HOME/mvg/config.yaml`. The old `~/.mvg.conf`
+/// is still used as long as it exists and the XDG location doesn't.
+pub fn config_location(flag: Option<&str>) -> PathBuf {
+    locate(
+        flag,
+        |var| std::env::var(var).ok().filter(|v| !v.is_empty()),
+        |path| path.exists(),
+    )
+}
+
+/// [`config_location`] with the environment and file system passed in
+pub fn locate<E, X>(flag: Option<&str>, env: E, exists: X) -> PathBuf
+where
+    E: Fn(&str) -> Option<String>,
+    X: Fn(&Path) -> bool,
+{
+    if let Some(flag) = flag {
+        return PathBuf::from(flag);
+    }
+    if let Some(location) = env(CONFIG_ENV) {
+        return PathBuf::from(location);
+    }
+    let home = env("HOME").map(PathBuf::from).unwrap_or_default();
+    // relative paths in XDG_CONFIG_HOME are invalid and have to be ignored
+    let config_home = env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home.join(".config"));
+    let xdg = config_home.join("mvg").join("config.yaml");
+    let legacy = home.join(".mvg.conf");
+    if !exists(&xdg) && exists(&legacy) {
+        legacy
+    } else {
+        xdg
+    }
 }
 
-pub fn load_config(location: &str) -> Config {
-    match std::fs::File::open(location) {
-        Ok(file) => match serde_yaml::from_reader(file) {
-            Ok(conf) => return conf,
-            Err(e) => println!("Failed to parse config file!\n{}", e),
-        },
-        Err(e) => println!("Failed to open config file!\n{}", e),
+/// Read the config file at `location`, a missing file is an empty config
+pub fn load_config(location: &Path) -> Result<Config, ConfigError> {
+    match std::fs::read_to_string(location) {
+        Ok(text) => parse_config(location, &text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(ConfigError::Io(location.to_path_buf(), e)),
     }
-    if let Ok(file) = std::fs::File::open(location) {
-        if let Ok(conf) = serde_yaml::from_reader(file) {
-            return conf;
+}
+
+/// Parse the content of the config file at `location`
+pub fn parse_config(location: &Path, text: &str) -> Result<Config, ConfigError> {
+    // an empty document is no mapping, but should be a valid config
+    if text.trim().is_empty() {
+        return Ok(Config::default());
+    }
+    serde_yaml::from_str(text).map_err(|e| ConfigError::Invalid(location.to_path_buf(), e))
+}
+
+/// Errors reading the config file
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Invalid(PathBuf, serde_yaml::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => {
+                write!(f, "Failed to read config file {}: {}", path.display(), e)
+            }
+            ConfigError::Invalid(path, e) => {
+                let message = e.to_string();
+                match e.location() {
+                    Some(location) => {
+                        // the message ends with the location as well
+                        let message = message
+                            .rfind(" at line ")
+                            .map_or(message.as_str(), |i| &message[..i]);
+                        write!(
+                            f,
+                            "{}:{}:{}: {}",
+                            path.display(),
+                            location.line(),
+                            location.column(),
+                            message
+                        )
+                    }
+                    None => write!(f, "{}: {}", path.display(), message),
+                }
+            }
         }
     }
-    Config::default()
 }
 
+impl std::error::Error for ConfigError {}
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Config {
     #[serde(default)]
     pub color_option: ColorOption,
     #[serde(default)]
     pub default_station: Option<String>,
+    /// Named stations, e.g. `home: de:09162:2`, usable wherever a station is expected
+    #[serde(default)]
+    pub favorites: BTreeMap<String, String>,
+    /// Minutes it takes to walk to a station, keyed by station id, name or favorite
+    #[serde(default)]
+    pub walk_minutes: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    /// Show times with a 12 hour clock
+    #[serde(default)]
+    pub twelve_hour: bool,
+    /// Timezone of shown and entered times, Munich's by default
+    #[serde(default)]
+    pub timezone: DisplayZone,
+    /// Transit network to ask
+    #[serde(default)]
+    pub provider: Provider,
+    /// MVG API to use, the legacy one falling back to the current one by default
+    #[serde(default)]
+    pub backend: Backend,
+}
+
+impl Config {
+    /// The station a favorite name stands for, other names are returned unchanged
+    pub fn station<'a>(&'a self, name: &'a str) -> &'a str {
+        self.favorites.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    /// Walking time of the first of `names` which has one
+    pub fn walk_minutes(&self, names: &[&str]) -> Option<u32> {
+        names.iter().find_map(|name| self.walk_minutes.get(*name).copied())
+    }
+
+    /// The value of `key`, `None` if it isn't set
+    pub fn get(&self, key: &ConfigKey) -> Option<serde_yaml::Value> {
+        let config = serde_yaml::to_value(self).ok()?;
+        let value = match key {
+            ConfigKey::Value(name) => config.get(name)?,
+            ConfigKey::Entry(name, entry) => config.get(name)?.get(entry.as_str())?,
+        };
+        Some(value.clone()).filter(|v| !v.is_null())
+    }
+}
+
+/// Commented config file written by `mvg config init`
+pub const TEMPLATE: &str = "\
+# Config file of mvg, see `mvg config --help`
+
+# Colors of the output: TrueColor, Ansi or No
+# color_option: TrueColor
+
+# Times as absolute (10:42), relative (in 4 min) or mixed
+# time_format: absolute
+
+# Show times with a 12 hour clock (10:42 AM)
+# twelve_hour: false
+
+# Timezone of shown and entered times: local or e.g. Europe/Berlin
+# timezone: Europe/Berlin
+
+# Transit network to ask: mvg
+# provider: mvg
+
+# MVG API to use: auto, fahrinfo (legacy) or bgw-pt (current)
+# backend: auto
+
+# Station used by `mvg departures` without a station
+# default_station: Marienplatz
+
+# Names usable wherever a station is expected, e.g. `mvg connections home work`
+favorites: {}
+
+# Minutes it takes to walk to a station (id, name or favorite), e.g. `home: 5`
+walk_minutes: {}
+";
+
+/// A setting as named by `mvg config get` and `mvg config set`
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigKey {
+    /// a top level setting like `color_option`
+    Value(String),
+    /// an entry of a map like `favorites.home`
+    Entry(String, String),
+}
+
+impl std::str::FromStr for ConfigKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let defaults = serde_yaml::to_value(Config::default()).map_err(|e| e.to_string())?;
+        let (name, entry) = match key.split_once('.') {
+            Some((name, entry)) => (name, Some(entry)),
+            None => (key, None),
+        };
+        let is_map = match defaults.get(name) {
+            Some(value) => value.is_mapping(),
+            None => return Err(format!("Unknown setting: {}", key)),
+        };
+        match (is_map, entry) {
+            (false, None) => Ok(ConfigKey::Value(name.to_string())),
+            (true, Some(entry)) if !entry.is_empty() => {
+                Ok(ConfigKey::Entry(name.to_string(), entry.to_string()))
+            }
+            (true, _) => Err(format!("{} is a map, use {}.<name>", name, name)),
+            (false, Some(_)) => Err(format!("{} is no map, use {}", name, name)),
+        }
+    }
+}
+
+/// Change the config file at `location` by editing its text.
+///
+/// The edited text has to be a valid config, otherwise the file is left
+/// untouched. The file is replaced atomically.
+pub fn edit_config_file<F>(path: &Path, edit: F) -> Result<(), String>
+where
+    F: FnOnce(&str) -> Result<String, String>,
+{
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ConfigError::Io(path.to_path_buf(), e).to_string()),
+    };
+    let text = edit(&text)?;
+    if let Err(e) = parse_config(path, &text) {
+        return Err(format!("Refusing to write invalid config\n{}", e));
+    }
+
+    let tmp = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    };
+    write().map_err(|e| format!("Failed to write config file {}: {}", path.display(), e))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorOption {
     TrueColor,
     Ansi,
@@ -44,7 +263,7 @@ pub enum ColorOption {
 
 impl Default for ColorOption {
     fn default() -> Self {
-        let color_env_var = std::env::var("COLORTERM").unwrap_or(String::new());
+        let color_env_var = std::env::var("COLORTERM").unwrap_or_default();
         if color_env_var.contains("truecolor") || color_env_var.contains("24bit") {
             ColorOption::TrueColor
         } else {
@@ -1,27 +1,93 @@
 use mvg_lib::data::location;
 use mvg_lib::data::MVGError;
 use mvg_lib::MVG;
+use mvg_lib::backend::Backend;
+use mvg_lib::provider::{Provider, TransitProvider};
 use mvg_lib::data::connection;
+use mvg_lib::data::departure::{Departure, DepartureFilter};
+use mvg_lib::data::station_id::StationId;
+use mvg_lib::resolve::{self, Resolution};
+use mvg_lib::routing::RoutingOptions;
+use mvg_lib::station_index::StationIndex;
+use mvg_lib::time_format::{self, DisplayZone, TimeFormat, TimeFormatter};
 
+use chrono::{DateTime, FixedOffset, NaiveDateTime, NaiveTime};
 use clap::Clap;
 use css_color_parser::Color as CssColor;
 use lazy_static::lazy_static;
-use termion::{color, style};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Mutex;
 
+mod board;
+mod cache;
 mod conf;
-use conf::Config;
+mod output;
+mod render;
+#[cfg(test)]
+mod test;
+mod watch;
+mod yaml_edit;
+use board::Board;
+use conf::{ColorOption, Config, ConfigKey};
+use output::Format;
+use render::Painter;
 
 const STATION_NAME_MAX_CHARS: usize = 40;
 
 lazy_static! {
-    static ref CONFIG: Config = conf::load_config(&conf::DEFAULT_LOCATION);
     static ref OPTS: Opts = Opts::parse();
+    static ref CONFIG_PATH: PathBuf = conf::config_location(OPTS.config.as_deref());
+    static ref CONFIG: Config = conf::load_config(&CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("{}\nUsing the default configuration.", e);
+        Config::default()
+    });
+    static ref PAINTER: Painter = if OPTS.format.is_structured() {
+        Painter::new(ColorOption::No)
+    } else {
+        Painter::for_stdout(CONFIG.color_option)
+    };
+    static ref TIME_ZONE: DisplayZone = OPTS.timezone.unwrap_or(CONFIG.timezone);
+    static ref TIME: TimeFormatter = TimeFormatter::new(OPTS.time_format.unwrap_or(CONFIG.time_format))
+        .twelve_hour(OPTS.twelve_hour || CONFIG.twelve_hour)
+        .zone(*TIME_ZONE);
+    static ref PROVIDER: Provider = OPTS.provider.unwrap_or(CONFIG.provider);
+    static ref STATION_INDEX_PATH: PathBuf = cache::index_location(*PROVIDER);
+    static ref STATION_INDEX: Mutex<StationIndex> = Mutex::new(cache::load(&STATION_INDEX_PATH));
+    static ref ERR_PAINTER: Painter = if OPTS.format.is_structured() {
+        Painter::new(ColorOption::No)
+    } else {
+        Painter::for_stderr(CONFIG.color_option)
+    };
 }
 
 /// Command line interface to Munich's public transportation service.
 #[derive(Clap)]
 #[clap(version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"))]
 struct Opts {
+    /// Output format: text, json, csv or yaml
+    #[clap(long, global = true, default_value = "text")]
+    format: Format,
+    /// Config file to use, overrides MVG_CONFIG
+    #[clap(long, global = true)]
+    config: Option<String>,
+    /// Times as absolute, relative or mixed, overrides time_format of the config
+    #[clap(long, global = true)]
+    time_format: Option<TimeFormat>,
+    /// Show times with a 12 hour clock
+    #[clap(long = "12h", global = true)]
+    twelve_hour: bool,
+    /// Timezone of shown and entered times: local or e.g. Europe/Berlin (default),
+    /// overrides timezone of the config
+    #[clap(long, global = true)]
+    timezone: Option<DisplayZone>,
+    /// Transit network to ask: mvg (default), overrides provider of the config
+    #[clap(long, global = true)]
+    provider: Option<Provider>,
+    /// MVG API to use: auto (default), fahrinfo or bgw-pt, overrides backend of the config
+    #[clap(long, global = true)]
+    backend: Option<Backend>,
     #[clap(subcommand)]
     subcmd: SubCommand,
 }
@@ -30,21 +96,76 @@ struct Opts {
 enum SubCommand {
     Stations(Stations),
     Departures(Departures),
-    Connections(Connections)
+    Connections(Connections),
+    Nearby(Nearby),
+    Interruptions(Interruptions),
+    Lines(Lines),
+    Fav(Fav),
+    Config(ConfigCommand),
 }
 
 /// Fetch stations
 #[derive(Clap)]
 struct Stations {
     /// Optional search term.
-    search_term: Option<String>
+    search_term: Option<String>,
+    /// Download all stations, to search and resolve station names offline.
+    /// Needs the fahrinfo API, the bgw-pt API can't list all stations.
+    #[clap(long, conflicts_with = "search-term")]
+    sync: bool,
 }
 
 /// Fetch departures
 #[derive(Clap)]
 struct Departures {
-    /// Either a station id or a station name.
-    station: Option<String>,
+    /// Station ids, names or favorites, several stations are shown on one board
+    stations: Vec<String>,
+    /// Only show departures of this product (ubahn, sbahn, tram, bus, bahn)
+    #[clap(long, number_of_values = 1)]
+    product: Vec<connection::Product>,
+    /// Only show departures of this line (e.g. U3)
+    #[clap(long, number_of_values = 1)]
+    line: Vec<String>,
+    /// Only show departures whose destination contains this text
+    #[clap(long)]
+    to: Option<String>,
+    /// Only show departures from this platform
+    #[clap(long, number_of_values = 1)]
+    platform: Vec<String>,
+    /// Show at most this many departures
+    #[clap(long)]
+    limit: Option<usize>,
+    /// Show a live departure board, quit with q
+    #[clap(long)]
+    watch: bool,
+    /// Seconds between refreshes of the live departure board
+    #[clap(long, default_value = "30")]
+    interval: u64,
+    /// Minutes it takes to walk to the stations, overrides walk_minutes of the config
+    #[clap(long)]
+    walk: Option<u32>,
+    /// Hide departures which can't be reached in time
+    #[clap(long)]
+    hide_unreachable: bool,
+}
+
+impl Departures {
+    fn filter(&self) -> DepartureFilter {
+        let mut filter = DepartureFilter::new();
+        for product in self.product.iter() {
+            filter = filter.product(*product);
+        }
+        for line in self.line.iter() {
+            filter = filter.line(line);
+        }
+        if let Some(to) = &self.to {
+            filter = filter.destination(to);
+        }
+        for platform in self.platform.iter() {
+            filter = filter.platform(platform);
+        }
+        filter
+    }
 }
 
 /// search connections
@@ -53,204 +174,939 @@ struct Connections{
     /// departure station
     from_station: String,
     /// destination station
-    to_station: String
+    to_station: String,
+    /// Depart at this time (HH:MM, YYYY-MM-DD HH:MM or RFC 3339 with offset)
+    #[clap(long, conflicts_with = "arrive-by")]
+    at: Option<TimeArg>,
+    /// Arrive by this time (HH:MM, YYYY-MM-DD HH:MM or RFC 3339 with offset)
+    #[clap(long)]
+    arrive_by: Option<TimeArg>,
+    /// Maximum number of changes
+    #[clap(long)]
+    max_changes: Option<u8>,
+    /// Don't use U-Bahn
+    #[clap(long)]
+    no_ubahn: bool,
+    /// Don't use S-Bahn
+    #[clap(long)]
+    no_sbahn: bool,
+    /// Don't use trams
+    #[clap(long)]
+    no_tram: bool,
+    /// Don't use busses
+    #[clap(long)]
+    no_bus: bool,
+    /// Show one line per connection instead of the full itinerary
+    #[clap(long)]
+    compact: bool,
+}
+
+impl Connections {
+    /// Times without offset are taken to be in `zone`
+    fn routing_options(&self, zone: DisplayZone) -> Result<RoutingOptions, String> {
+        let mut options = RoutingOptions::new();
+        if let Some(at) = &self.at {
+            options = options.depart_at(at.resolve(zone)?);
+        }
+        if let Some(arrive_by) = &self.arrive_by {
+            options = options.arrive_by(arrive_by.resolve(zone)?);
+        }
+        if let Some(max_changes) = self.max_changes {
+            options = options.max_changes(max_changes);
+        }
+        let excluded = [
+            (self.no_ubahn, connection::Product::UBahn),
+            (self.no_sbahn, connection::Product::SBahn),
+            (self.no_tram, connection::Product::Tram),
+            (self.no_bus, connection::Product::Bus),
+        ];
+        for (_, product) in excluded.iter().filter(|(excluded, _)| *excluded) {
+            options = options.exclude(*product);
+        }
+        Ok(options)
+    }
+}
+
+/// A time given on the command line, as HH:MM (today), YYYY-MM-DD HH:MM or
+/// RFC 3339 with offset
+#[derive(Debug, Clone, PartialEq)]
+enum TimeArg {
+    Today(NaiveTime),
+    Naive(NaiveDateTime),
+    Zoned(DateTime<FixedOffset>),
+}
+
+impl std::str::FromStr for TimeArg {
+    type Err = String;
+
+    fn from_str(time: &str) -> Result<Self, Self::Err> {
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map(TimeArg::Today)
+            .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").map(TimeArg::Naive))
+            .or_else(|_| DateTime::parse_from_rfc3339(time).map(TimeArg::Zoned))
+            .map_err(|_| {
+                format!(
+                    "Invalid time: {} (expected HH:MM, YYYY-MM-DD HH:MM or RFC 3339)",
+                    time
+                )
+            })
+    }
+}
+
+impl TimeArg {
+    /// The time, taking times without offset to be in `zone`
+    fn resolve(&self, zone: DisplayZone) -> Result<DateTime<FixedOffset>, String> {
+        let naive = match self {
+            TimeArg::Zoned(time) => return Ok(*time),
+            TimeArg::Naive(naive) => *naive,
+            TimeArg::Today(time) => zone.date_of(&TIME.now()).and_time(*time),
+        };
+        zone.from_naive(&naive)
+            .ok_or_else(|| format!("{} doesn't exist in timezone {}", naive, zone))
+    }
+}
+
+/// List stations near a position
+#[derive(Clap)]
+struct Nearby {
+    /// latitude of the position
+    latitude: f64,
+    /// longitude of the position
+    longitude: f64,
+}
+
+/// Show current service interruptions
+#[derive(Clap)]
+struct Interruptions {
+    /// Only show interruptions affecting this line (e.g. U3)
+    #[clap(long)]
+    line: Option<String>,
+    /// Only show interruptions affecting this product (ubahn, sbahn, tram, bus, bahn)
+    #[clap(long)]
+    product: Option<connection::Product>,
+}
+
+/// List the lines serving a station
+#[derive(Clap)]
+struct Lines {
+    /// Station id, name or favorite
+    station: String,
+}
+
+/// Manage favorite stations
+#[derive(Clap)]
+struct Fav {
+    #[clap(subcommand)]
+    action: FavAction,
+}
+
+#[derive(Clap)]
+enum FavAction {
+    /// Add a favorite or change its station
+    Add(FavAdd),
+    /// Remove a favorite
+    Remove(FavRemove),
+    /// List all favorites
+    List,
+}
+
+#[derive(Clap)]
+struct FavAdd {
+    /// Name of the favorite, e.g. home
+    name: String,
+    /// Station id or name
+    station: String,
+}
+
+#[derive(Clap)]
+struct FavRemove {
+    /// Name of the favorite
+    name: String,
+}
+
+/// Show and change the configuration
+#[derive(Clap)]
+struct ConfigCommand {
+    #[clap(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(Clap)]
+enum ConfigAction {
+    /// Create a commented config file
+    Init(ConfigInit),
+    /// Show the configuration in effect
+    Show,
+    /// Show one setting, e.g. color_option or favorites.home
+    Get(ConfigGet),
+    /// Change one setting, e.g. color_option or favorites.home
+    Set(ConfigSet),
+    /// Show the location of the config file
+    Path,
+}
+
+#[derive(Clap)]
+struct ConfigInit {
+    /// Replace an existing config file
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Clap)]
+struct ConfigGet {
+    key: ConfigKey,
+}
+
+#[derive(Clap)]
+struct ConfigSet {
+    key: ConfigKey,
+    value: String,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
-    let mvg = MVG::new();
-
     match &OPTS.subcmd {
+        SubCommand::Stations(s) if s.sync => {
+            sync_stations(&*client()).await;
+        }
         SubCommand::Stations(s) => {
             print_stations(
                 &match &s.search_term {
                     Some(s) => String::from(s),
                     None => String::new(),
                 },
-                &mvg,
+                &*client(),
             )
             .await;
         }
         SubCommand::Departures(d) => {
-            let station = d.station.as_ref().or(CONFIG.default_station.as_ref());
-            if let Some(station) = station {
-                print_departures(&station, &mvg).await;
+            let stations = if d.stations.is_empty() {
+                CONFIG.default_station.iter().cloned().collect()
+            } else {
+                d.stations.clone()
+            };
+            if !stations.is_empty() {
+                print_departures(&stations, d, &*client()).await;
             } else {
                 println!("Please provide a station!");
             }
         }
         SubCommand::Connections(c) => {
-            print_connections(&c.from_station, &c.to_station, &mvg).await;
+            match c.routing_options(*TIME_ZONE) {
+                Ok(options) => print_connections(&c.from_station, &c.to_station, &options, c.compact, &*client()).await,
+                Err(e) => {
+                    eprintln!("{}: {}", ERR_PAINTER.red("Err"), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        SubCommand::Nearby(n) => {
+            print_nearby(n.latitude, n.longitude, &*client()).await;
+        }
+        SubCommand::Interruptions(i) => {
+            print_interruptions(i, &*client()).await;
+        }
+        SubCommand::Lines(l) => {
+            print_lines(&l.station, &*client()).await;
+        }
+        SubCommand::Fav(f) => {
+            manage_favorites(&f.action, &*client()).await;
+        }
+        SubCommand::Config(c) => {
+            manage_config(&c.action);
         }
     };
 
     Ok(())
 }
 
-async fn print_stations(search_string: &str, mvg: &MVG) {
-    let stations = match mvg.stations_by_name(search_string).await {
-        Ok(stations) => stations,
-        Err(e) => {
-            print_mvg_err(&e);
-            return;
-        }
-    };
-    for sta in stations.iter().filter_map(|s| match s {
-        location::Location::Station(s) => Some(s),
-        location::Location::Address(_) => None,
-        location::Location::Location(_) => None
-    }) {
-        println!("{}, {}, {}", sta.name(), sta.place(), sta.id())
+/// Client of the provider chosen by the flag or the config
+fn client() -> Box<dyn TransitProvider> {
+    match *PROVIDER {
+        Provider::Mvg => Box::new(
+            MVG::builder()
+                .backend(OPTS.backend.unwrap_or(CONFIG.backend))
+                .build(),
+        ),
     }
 }
 
-async fn print_departures(search_string: &str, mvg: &MVG) {
-    let stations = match mvg.stations_by_id(search_string).await {
-        Ok(stations) => stations,
-        Err(_) => match mvg.stations_by_name(search_string).await {
-            Ok(stations) => stations,
+async fn print_stations(search_string: &str, provider: &dyn TransitProvider) {
+    let known = {
+        let index = STATION_INDEX.lock().unwrap();
+        Some(index.search(search_string)).filter(|_| index.is_complete())
+    };
+    let stations = match known {
+        Some(stations) => stations,
+        None => match provider.stations_by_name(search_string).await {
+            Ok(locations) => {
+                remember_stations(&locations);
+                locations
+                    .into_iter()
+                    .filter_map(|l| match l {
+                        location::Location::Station(s) => Some(s),
+                        _ => None,
+                    })
+                    .collect()
+            }
             Err(e) => {
                 print_mvg_err(&e);
-                return;
+                std::process::exit(1);
             }
         },
     };
+    if OPTS.format.is_structured() {
+        let records: Vec<_> = stations.iter().map(output::StationRecord::from).collect();
+        print_records(&records);
+        return;
+    }
+    for sta in stations {
+        println!("{}, {}, {}", sta.name(), sta.place(), sta.id())
+    }
+}
 
-    // filter for stations
-    let mut stations = stations.iter().filter_map(|s| match s {
-        location::Location::Station(s) => Some(s),
-        _ => None,
-    });
-
-    let station = match stations.next() {
-        Some(station) => station,
-        None => {
-            println!("No station found");
-            return;
+/// Replace the station index by all stations of the provider
+async fn sync_stations(provider: &dyn TransitProvider) {
+    let index = match provider.stations_by_name("").await {
+        Ok(locations) => StationIndex::complete(&locations),
+        Err(e) => {
+            print_mvg_err(&e);
+            std::process::exit(1);
         }
     };
+    if index.is_empty() {
+        eprintln!("{}: {} didn't list any stations", ERR_PAINTER.red("Err"), provider.name());
+        std::process::exit(1);
+    }
+    if let Err(e) = index.save(&STATION_INDEX_PATH) {
+        eprintln!(
+            "{}: Couldn't write {}: {}",
+            ERR_PAINTER.red("Err"),
+            STATION_INDEX_PATH.display(),
+            e
+        );
+        std::process::exit(1);
+    }
+    println!("Saved {} stations to {}", index.len(), STATION_INDEX_PATH.display());
+}
 
-    let departures = match mvg.departures_by_id(&station.id()).await {
-        Ok(departures) => departures,
+/// Add stations from an API response to the station index
+fn remember_stations(locations: &[location::Location]) {
+    let mut index = STATION_INDEX.lock().unwrap();
+    cache::remember(&mut index, &STATION_INDEX_PATH, locations);
+}
+
+async fn print_nearby(latitude: f64, longitude: f64, provider: &dyn TransitProvider) {
+    let nearby = match provider.stations_nearby(latitude, longitude).await {
+        Ok(nearby) => nearby,
         Err(e) => {
             print_mvg_err(&e);
+            std::process::exit(1);
+        }
+    };
+    // the library returns the closest locations first
+    if OPTS.format.is_structured() {
+        let records: Vec<_> = nearby.iter().filter_map(output::NearbyRecord::new).collect();
+        print_records(&records);
+        return;
+    }
+    for n in nearby.iter() {
+        if let location::Location::Station(sta) = n.location() {
+            println!("{:>5.0} m\t{}, {}, {}", n.distance(), sta.name(), sta.place(), sta.id())
+        }
+    }
+}
+
+async fn print_departures(search_strings: &[String], opts: &Departures, provider: &dyn TransitProvider) {
+    let mut stations = Vec::new();
+    let mut walk_minutes = HashMap::new();
+    for search_string in search_strings {
+        let station = find_station(search_string, provider).await;
+        let walk = opts.walk.or_else(|| {
+            CONFIG.walk_minutes(&[
+                search_string,
+                CONFIG.station(search_string),
+                station.id().as_str(),
+                &station.name(),
+            ])
+        });
+        if let Some(walk) = walk {
+            walk_minutes.insert(station.id().clone(), walk);
+        }
+        stations.push(station);
+    }
+    let board = Board::new(stations, walk_minutes);
+
+    if opts.watch {
+        if OPTS.format.is_structured() {
+            eprintln!("The live departure board only supports text output");
             return;
         }
+        if let Err(e) = watch::watch_departures(&board, opts, provider).await {
+            eprintln!("{}: {}", ERR_PAINTER.red("Err"), e);
+        }
+        return;
+    }
+
+    let departures = provider.departures_multi(&board.station_ids()).await;
+    for (id, e) in departures.errors() {
+        eprint!("{}: ", board.station_name(id));
+        print_mvg_err(e);
+    }
+    if departures.errors().len() == board.stations().len() {
+        std::process::exit(1);
+    }
+    let now = TIME.now();
+    let filter = opts.filter();
+    let departures = departures
+        .departures()
+        .iter()
+        .filter(|(_, d)| filter.matches(d))
+        .filter(|(id, d)| !opts.hide_unreachable || board.reachable(id, d, now))
+        .take(opts.limit.unwrap_or(usize::MAX));
+    if OPTS.format.is_structured() {
+        let records: Vec<_> = departures
+            .map(|(id, d)| output::DepartureRecord::new(id, d, board.leave_in(id, d, now)))
+            .collect();
+        print_records(&records);
+        return;
+    }
+    println!("{}:", board.title());
+    for (id, dep) in departures {
+        println!("{}", board.line(id, dep, false, now));
+    }
+}
+
+/// The lines of a station, grouped by product. The lines the station lists
+/// itself are completed by the ones the provider knows from its departures.
+async fn print_lines(search_string: &str, provider: &dyn TransitProvider) {
+    let station = find_station(search_string, provider).await;
+    let mut lines = station.lines().clone();
+    match provider.serving_lines(station.id()).await {
+        Ok(serving_lines) => {
+            for line in serving_lines.iter() {
+                lines.add(line.group(), line.line_number());
+            }
+        }
+        Err(e) if lines.is_empty() => {
+            print_mvg_err(&e);
+            std::process::exit(1);
+        }
+        Err(_) => {}
+    }
+    if OPTS.format.is_structured() {
+        let records = output::LineRecord::of_station(&station, &lines);
+        print_records(&records);
+        return;
+    }
+    if lines.is_empty() {
+        println!("No lines known for station {}, {}", station.name(), station.place());
+        return;
+    }
+    println!("Lines at station {}, {}:", station.name(), station.place());
+    let width = lines
+        .groups()
+        .iter()
+        .map(|(group, _)| group.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for (group, labels) in lines.groups() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|label| PAINTER.label(label, &line_color(label, group.product())))
+            .collect();
+        println!("{}  {}", fit(group.to_string(), width), labels.join(" "));
+    }
+}
+
+/// `text` cut or padded to `width` characters
+fn fit(text: String, width: usize) -> String {
+    let len = text.chars().count();
+    if len > width {
+        format!("{}...", text.chars().take(width.saturating_sub(3)).collect::<String>())
+    } else {
+        let mut text = text;
+        text.extend((len..width).map(|_| ' '));
+        text
+    }
+}
+
+/// Look up the station meant by `search_string`, which is either a favorite,
+/// a station id or a name. Ambiguous names are resolved by asking the user if
+/// stdin is a terminal. Exits the program if no single station can be determined.
+async fn find_station(search_string: &str, provider: &dyn TransitProvider) -> location::Station {
+    let search_string = CONFIG.station(search_string);
+    let id = search_string.parse::<StationId>().ok();
+    let known = {
+        let index = STATION_INDEX.lock().unwrap();
+        match &id {
+            Some(id) => index.by_id(id).cloned().map(Resolution::Unique),
+            None => index.resolve(search_string),
+        }
     };
-    println!(
-        "Departures at station {}, {}:",
-        station.name(),
-        station.place()
-    );
-    for dep in departures {
-        let color = dep
-            .line_background_color()
-            .parse::<CssColor>()
-            .unwrap_or(CssColor {
-                r: 255,
-                g: 255,
-                b: 255,
-                a: 1.0,
-            });
-
-        let adjust = |col| std::cmp::min((col as u16 + 32) / 64, 4) as u8;
-
-        //let color = color::Rgb(color.r, color.g, color.b);
-        let color = color::AnsiValue::rgb(adjust(color.r), adjust(color.g), adjust(color.b));
-
-        print!("{}{}{}\t", color::Bg(color), dep.label(), style::Reset);
-        //print!("{}\t", dep.label().on_truecolor(color.r, color.g, color.b));
-
-        let destination = dep.destination();
-        let dest_len = destination.chars().count();
-
-        if dest_len > STATION_NAME_MAX_CHARS {
-            print!(
-                "{}...",
-                destination
-                    .chars()
-                    .take(STATION_NAME_MAX_CHARS - 3)
-                    .collect::<String>()
-            );
-        } else {
-            print!("{}", destination);
-            print!(
-                "{}",
-                (dest_len..STATION_NAME_MAX_CHARS)
-                    .map(|_| ' ')
-                    .collect::<String>()
-            );
+    let resolution = match (known, id) {
+        (Some(resolution), _) => resolution,
+        (None, Some(id)) => lookup_station_id(&id, provider).await,
+        (None, None) => lookup_station(search_string, provider).await,
+    };
+    match resolution {
+        Resolution::Unique(station) => station,
+        Resolution::NotFound => {
+            eprintln!("{}: No station found for \"{}\"", ERR_PAINTER.red("Err"), search_string);
+            std::process::exit(1);
+        }
+        Resolution::Ambiguous(candidates) => {
+            if termion::is_tty(&std::io::stdin()) {
+                pick_station(candidates)
+            } else {
+                eprintln!(
+                    "{}: \"{}\" matches several stations:",
+                    ERR_PAINTER.red("Err"),
+                    search_string
+                );
+                for sta in candidates.iter() {
+                    eprintln!("  {}", describe_station(sta));
+                }
+                eprintln!("Use the station id or \"name, place\" to select one.");
+                std::process::exit(1);
+            }
         }
+    }
+}
 
-        print!("{}", dep.departure_time().format("%_H:%M"));
-        println!();
+async fn manage_favorites(action: &FavAction, provider: &dyn TransitProvider) {
+    let result = match action {
+        FavAction::Add(add) => {
+            // store the id, names might become ambiguous later
+            let station = find_station(&add.station, provider).await;
+            conf::edit_config_file(&CONFIG_PATH, |text| {
+                Ok(yaml_edit::set_entry(text, "favorites", &add.name, &yaml_edit::string(station.id().as_str())))
+            })
+            .map(|_| println!("{} is now {}", add.name, describe_station(&station)))
+        }
+        FavAction::Remove(remove) => conf::edit_config_file(&CONFIG_PATH, |text| {
+            yaml_edit::remove_entry(text, "favorites", &remove.name)
+                .ok_or_else(|| format!("No favorite named \"{}\"", remove.name))
+        }),
+        FavAction::List => {
+            if OPTS.format.is_structured() {
+                let records: Vec<_> = CONFIG
+                    .favorites
+                    .iter()
+                    .map(|(name, station)| output::FavoriteRecord::new(name, station))
+                    .collect();
+                print_records(&records);
+            } else {
+                for (name, station) in CONFIG.favorites.iter() {
+                    println!("{}\t{}", name, station);
+                }
+            }
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}: {}", ERR_PAINTER.red("Err"), e);
+        std::process::exit(1);
+    }
+}
+
+fn manage_config(action: &ConfigAction) {
+    let result = match action {
+        ConfigAction::Init(init) => init_config(init.force),
+        ConfigAction::Show => load_config().and_then(|config| match OPTS.format {
+            Format::Json => serde_json::to_string_pretty(&config)
+                .map(|json| println!("{}", json))
+                .map_err(|e| e.to_string()),
+            // the config is nested, it doesn't fit into rows
+            Format::Csv => Err(String::from("config show supports text, json and yaml, not csv")),
+            Format::Text | Format::Yaml => serde_yaml::to_string(&config)
+                .map(|yaml| println!("{}", yaml.trim_start_matches("---\n").trim_end()))
+                .map_err(|e| e.to_string()),
+        }),
+        ConfigAction::Get(get) => load_config().and_then(|config| match config.get(&get.key) {
+            Some(serde_yaml::Value::String(s)) => {
+                println!("{}", s);
+                Ok(())
+            }
+            Some(value) => serde_yaml::to_string(&value)
+                .map(|yaml| println!("{}", yaml.trim_start_matches("---\n").trim_end()))
+                .map_err(|e| e.to_string()),
+            None => Err(String::from("Not set")),
+        }),
+        ConfigAction::Set(set) => conf::edit_config_file(&CONFIG_PATH, |text| {
+            let edit = |value: &str| match &set.key {
+                ConfigKey::Value(name) => yaml_edit::set_value(text, name, value),
+                ConfigKey::Entry(name, entry) => yaml_edit::set_entry(text, name, entry, value),
+            };
+            // numbers and the like are written as they are if the setting takes them
+            let plain = edit(&set.value);
+            let valid = !set.value.contains(['\n', '#'])
+                && conf::parse_config(&CONFIG_PATH, &plain).is_ok();
+            if valid {
+                Ok(plain)
+            } else {
+                Ok(edit(&yaml_edit::string(&set.value)))
+            }
+        }),
+        ConfigAction::Path => {
+            println!("{}", CONFIG_PATH.display());
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        // ERR_PAINTER would load the config which might be broken
+        let painter = Painter::for_stderr(ColorOption::default());
+        eprintln!("{}: {}", painter.red("Err"), e);
+        std::process::exit(1);
+    }
+}
+
+/// The config file, unlike [`CONFIG`] without falling back to the defaults
+fn load_config() -> Result<Config, String> {
+    conf::load_config(&CONFIG_PATH).map_err(|e| e.to_string())
+}
+
+fn init_config(force: bool) -> Result<(), String> {
+    if CONFIG_PATH.exists() && !force {
+        return Err(format!(
+            "{} exists already, use --force to replace it",
+            CONFIG_PATH.display()
+        ));
+    }
+    if let Some(dir) = CONFIG_PATH.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
     }
+    std::fs::write(&*CONFIG_PATH, conf::TEMPLATE)
+        .map_err(|e| format!("Failed to write {}: {}", CONFIG_PATH.display(), e))?;
+    println!("Created {}", CONFIG_PATH.display());
+    Ok(())
 }
 
-async fn print_connections(from: &str, to: &str, mvg: &MVG){
-    let from: Vec<mvg_lib::data::location::Station> = match mvg.stations_by_name(from).await{
-        Ok(stations) => stations,
+/// Look up the station with `id` with the API, remembering the stations found
+async fn lookup_station_id(id: &StationId, provider: &dyn TransitProvider) -> Resolution {
+    let locations = match provider.stations_by_id(id).await {
+        Ok(locations) => locations,
+        Err(MVGError::NotFound { .. }) if !id.is_global() => Vec::new(),
         Err(e) => {
             print_mvg_err(&e);
-            return;
+            std::process::exit(1);
         }
-    }.into_iter().filter_map(|s| match s {
-        location::Location::Station(s) => Some(s),
+    };
+    remember_stations(&locations);
+    // the API may return similar stations as well
+    let station = locations.into_iter().find_map(|s| match s {
+        location::Location::Station(s) if s.has_id(id) => Some(s),
         _ => None,
-    }).collect();
-
-    let from = from.first().unwrap();
+    });
+    match station {
+        Some(station) => Resolution::Unique(station),
+        // the API doesn't always know bare DIVA ids, they might be part of a name
+        None if !id.is_global() => lookup_station(id.as_str(), provider).await,
+        None => Resolution::NotFound,
+    }
+}
 
-    let to: Vec<mvg_lib::data::location::Station> = match mvg.stations_by_name(to).await{
-        Ok(stations) => stations,
+/// Resolve the station name `search_string` with the API, remembering the
+/// stations found
+async fn lookup_station(search_string: &str, provider: &dyn TransitProvider) -> Resolution {
+    match provider.stations_by_name(search_string).await {
+        Ok(locations) => {
+            remember_stations(&locations);
+            resolve::resolve_station(search_string, locations)
+        }
         Err(e) => {
             print_mvg_err(&e);
-            return;
+            std::process::exit(1);
         }
-    }.into_iter().filter_map(|s| match s {
-        location::Location::Station(s) => Some(s),
-        _ => None,
-    }).collect();
+    }
+}
+
+/// Let the user choose one of `candidates` interactively
+fn pick_station(mut candidates: Vec<location::Station>) -> location::Station {
+    eprintln!("Several stations match, please choose one:");
+    for (i, sta) in candidates.iter().enumerate() {
+        eprintln!("{:>3}) {}", i + 1, describe_station(sta));
+    }
+    loop {
+        eprint!("Station [1-{}, default 1]: ", candidates.len());
+        let mut input = String::new();
+        match std::io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => std::process::exit(1),
+            Ok(_) => {}
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            return candidates.remove(0);
+        }
+        match input.parse::<usize>() {
+            Ok(i) if i >= 1 && i <= candidates.len() => return candidates.remove(i - 1),
+            _ => eprintln!("Please enter a number between 1 and {}", candidates.len()),
+        }
+    }
+}
 
-    let to = to.first().unwrap();
+/// Name, place, id and products of a station on one line
+fn describe_station(station: &location::Station) -> String {
+    let products: Vec<String> = station.products().iter().map(|p| p.to_string()).collect();
+    format!(
+        "{}, {} ({}) {}",
+        station.name(),
+        station.place(),
+        station.id(),
+        products.join(" ")
+    )
+}
 
-    let connections = mvg.connections(&from.id(), &to.id()).await;
+/// One line of the departure board, `highlight_delay` marks a changed delay
+fn format_departure(dep: &Departure, highlight_delay: bool) -> String {
+    let mut line = String::new();
+    let color = dep
+        .line_background_color()
+        .parse::<CssColor>()
+        .unwrap_or(WHITE);
+
+    let _ = write!(line, "{}\t", PAINTER.label(&dep.label(), &color));
+
+    let mut destination = fit(dep.destination(), STATION_NAME_MAX_CHARS);
+    let mut time = format!("{:>width$}", TIME.departure(dep), width = TIME.width());
+    if dep.cancelled() {
+        destination = PAINTER.crossed_out(&destination);
+        time = PAINTER.crossed_out(&time);
+    }
+    line.push_str(&destination);
+    line.push_str(&time);
+
+    let delay = format!(" {}", format_delay(dep.delay()));
+    if highlight_delay {
+        line.push_str(&PAINTER.inverted(&delay));
+    } else {
+        line.push_str(&delay);
+    }
+    if let Some(platform) = dep.platform() {
+        let _ = write!(line, "\tPl. {}", platform);
+    }
+    if dep.sev() {
+        let _ = write!(line, "\t{}", PAINTER.bold("SEV"));
+    }
+    if dep.cancelled() {
+        line.push_str("\tcancelled");
+    }
+    line
+}
+
+async fn print_connections(from: &str, to: &str, options: &RoutingOptions, compact: bool, provider: &dyn TransitProvider){
+    let from = find_station(from, provider).await;
+    let to = find_station(to, provider).await;
+
+    let connections = provider.connections_with(from.id(), to.id(), options).await;
     if let Err(e) = &connections {
         print_mvg_err(e);
-        println!("{:#?}", e);
-        return;
+        std::process::exit(1);
     }
     let connections = connections.unwrap();
-    for (i, con) in connections.into_iter().enumerate(){
-        println!{"Connection {}", i};
-        for con_part in con.connection_parts(){
-            match con_part{
-                connection::ConnectionPart::Footway(_fw) => {
-                    println!("Run!");
+    // the options may have been dropped by the API which answered
+    for option in provider.ignored_routing_options(options) {
+        eprintln!("{}: the API which answered ignored the {}", ERR_PAINTER.bold("Warning"), option);
+    }
+    if OPTS.format.is_structured() {
+        let records: Vec<_> = connections
+            .iter()
+            .enumerate()
+            .flat_map(|(i, con)| output::ConnectionLegRecord::legs(i, con))
+            .collect();
+        print_records(&records);
+        return;
+    }
+    for (i, con) in connections.iter().enumerate(){
+        if compact {
+            print_connection_compact(i + 1, con);
+        } else {
+            print_connection(i + 1, con);
+        }
+    }
+}
+
+/// Full itinerary of a connection
+fn print_connection(number: usize, con: &connection::Connection) {
+    println!(
+        "{} {} → {}, {}, {}",
+        PAINTER.bold(&format!("Connection {}:", number)),
+        TIME.absolute(&con.departure_time()),
+        TIME.absolute(&con.arrival_time()),
+        format_duration(con.duration()),
+        format_changes(con.changes())
+    );
+    for con_part in con.connection_parts(){
+        match con_part{
+            connection::ConnectionPart::Footway(fw) => {
+                println!(
+                    "  {} {} Walk\t{} to {}",
+                    TIME.absolute(&fw.departure_time()),
+                    format_delay(None),
+                    format_duration(fw.duration()),
+                    output::location_name(fw.to())
+                );
+            }
+            connection::ConnectionPart::Transportation(tp) => {
+                let label = PAINTER.label(tp.label(), &line_color(tp.label(), Some(*tp.product())));
+                let mut from = output::location_name(tp.from());
+                if let Some(platform) = tp.departure_platform() {
+                    from = format!("{}, Pl. {}", from, platform);
+                }
+                let mut to = output::location_name(tp.to());
+                if let Some(platform) = tp.arrival_platform() {
+                    to = format!("{}, Pl. {}", to, platform);
                 }
-                connection::ConnectionPart::Transportation(tp) => {
-                    if let location::Location::Station(from) = tp.from(){
-                        if let location::Location::Station(to) = tp.to(){
-                            println!("Take {} from {} to {}", tp.label(), from.name(), to.name());
-                        }
-                    }
+                let mut direction = format!("direction {}", tp.destination());
+                if tp.sev() {
+                    direction = format!("{} {}", direction, PAINTER.bold("SEV"));
+                }
+                if tp.cancelled() {
+                    from = PAINTER.crossed_out(&from);
+                    direction = format!("{} cancelled", direction);
+                }
+                println!(
+                    "  {} {} {}\t{}",
+                    TIME.absolute(&tp.departure_time()),
+                    format_delay(Some(tp.delay())),
+                    label,
+                    from
+                );
+                println!("                \t{}", direction);
+                println!(
+                    "  {} {}     \t{}",
+                    TIME.absolute(&tp.arrival_time()),
+                    format_delay(Some(tp.arrival_delay())),
+                    to
+                );
+                for message in tp.info_messages() {
+                    println!("                \t{} {}", PAINTER.bold("!"), message);
                 }
             }
         }
     }
+    println!();
 }
 
-fn print_mvg_err(err: &MVGError) {
+/// One line summary of a connection
+fn print_connection_compact(number: usize, con: &connection::Connection) {
+    let parts: Vec<String> = con
+        .connection_parts()
+        .iter()
+        .map(|part| match part {
+            connection::ConnectionPart::Footway(fw) => {
+                format!("walk {}", format_duration(fw.duration()))
+            }
+            connection::ConnectionPart::Transportation(tp) => {
+                PAINTER.label(tp.label(), &line_color(tp.label(), Some(*tp.product())))
+            }
+        })
+        .collect();
+    let delay = format_delay(con.transportations().next().map(|tp| tp.delay()));
     println!(
-        "{}Err{}: {}",
-        color::Fg(color::Red),
-        style::Reset,
-        match err {
-            MVGError::HyperError(_) => "Couldn't connect to the MVG API.",
-            MVGError::JsonError(_) => "Couldn't parse API response.",
-            MVGError::InvalidUri(_) => "Couldn't create valid URI.",
-            _ => "Unknown Error",
+        "{:>2}  {} {} → {}  {:>7}  {:<10}  {}",
+        number,
+        TIME.absolute(&con.departure_time()),
+        delay,
+        TIME.absolute(&con.arrival_time()),
+        format_duration(con.duration()),
+        format_changes(con.changes()),
+        parts.join(" › ")
+    );
+}
+
+/// Delay as `+3`, blank if there is none, always 4 characters wide
+fn format_delay(delay: Option<chrono::Duration>) -> String {
+    let text = format!("{:<4}", time_format::format_delay(delay));
+    if delay.is_some_and(|d| d.num_minutes() > 0) {
+        PAINTER.red(&text)
+    } else {
+        text
+    }
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes >= 60 {
+        format!("{}h {:02}min", minutes / 60, minutes % 60)
+    } else {
+        format!("{} min", minutes)
+    }
+}
+
+fn format_changes(changes: usize) -> String {
+    match changes {
+        1 => String::from("1 change"),
+        n => format!("{} changes", n),
+    }
+}
+
+async fn print_interruptions(opts: &Interruptions, provider: &dyn TransitProvider) {
+    let interruptions = match provider.interruptions().await {
+        Ok(interruptions) => interruptions,
+        Err(e) => {
+            print_mvg_err(&e);
+            std::process::exit(1);
         }
-    )
+    };
+    let interruptions = interruptions.iter().filter(|i| {
+        opts.line.as_ref().is_none_or(|l| i.affects_line(l))
+            && opts.product.is_none_or(|p| i.affects_product(p))
+    });
+    if OPTS.format.is_structured() {
+        let records: Vec<_> = interruptions.map(output::InterruptionRecord::from).collect();
+        print_records(&records);
+        return;
+    }
+    for int in interruptions {
+        for line in int.affected_lines() {
+            print!("{} ", PAINTER.label(line.label(), &line_color(line.label(), line.product())));
+        }
+        println!("{}", PAINTER.bold(int.title()));
+        if !int.validity_text().is_empty() {
+            println!("{}", int.validity_text());
+        }
+        println!("{}", int.plain_text());
+        println!();
+    }
+}
+
+const WHITE: CssColor = CssColor {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 1.0,
+};
+
+/// Line color for places where the API doesn't deliver one
+fn line_color(label: &str, product: Option<connection::Product>) -> CssColor {
+    let hex = match label {
+        "U1" | "U7" => "#52822f",
+        "U2" | "U8" => "#c20831",
+        "U3" => "#ec6726",
+        "U4" => "#00a984",
+        "U5" => "#bc7a00",
+        "U6" => "#0065ae",
+        _ => match product {
+            Some(connection::Product::UBahn) => "#0065ae",
+            Some(connection::Product::SBahn) => "#408335",
+            Some(connection::Product::Tram) => "#e30613",
+            Some(connection::Product::Bus) => "#00586a",
+            Some(connection::Product::Bahn) => "#e30613",
+            Some(connection::Product::Other) => "#808080",
+            None => "#ffffff",
+        },
+    };
+    hex.parse::<CssColor>().unwrap_or(WHITE)
+}
+
+fn print_mvg_err(err: &MVGError) {
+    eprintln!("{}: {}", ERR_PAINTER.red("Err"), err)
+}
+
+fn print_records<T: serde::Serialize>(records: &[T]) {
+    if let Err(e) = output::print_records(OPTS.format, records) {
+        eprintln!("Err: Couldn't write output: {}", e);
+    }
 }
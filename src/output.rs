@@ -0,0 +1,343 @@
+//! Machine readable output of the subcommands.
+//!
+//! Every subcommand prints a list of flat records, so the same schema works
+//! for JSON, YAML and CSV. Times are RFC 3339 strings with UTC offset, lists
+//! (like the products of a station) are space separated. Fields are only
+//! ever added, never renamed or removed.
+
+use std::error::Error;
+use std::io;
+
+use mvg_lib::data::connection::{Connection, ConnectionPart, Product};
+use mvg_lib::data::departure::Departure;
+use mvg_lib::data::interruption::Interruption;
+use mvg_lib::data::line::Lines;
+use mvg_lib::data::location::{Location, NearbyLocation, Station};
+use mvg_lib::data::station_id::StationId;
+use serde::Serialize;
+
+/// Output format selected with `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable, colored output
+    Text,
+    Json,
+    Csv,
+    Yaml,
+}
+
+impl Format {
+    /// whether the output is meant for other programs
+    pub fn is_structured(self) -> bool {
+        self != Format::Text
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(format!("Unknown format: {} (expected text, json, csv or yaml)", s)),
+        }
+    }
+}
+
+/// Print `records` to stdout in a structured format.
+///
+/// Text output is rendered by the subcommands themselves.
+pub fn print_records<T: Serialize>(format: Format, records: &[T]) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(stdout, records)?;
+            println!();
+        }
+        Format::Yaml => {
+            serde_yaml::to_writer(stdout, records)?;
+            println!();
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(stdout);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Text => unreachable!("text output is rendered by the subcommands"),
+    }
+    Ok(())
+}
+
+/// A station, output of `mvg stations`
+#[derive(Serialize)]
+pub struct StationRecord {
+    pub id: StationId,
+    pub name: String,
+    pub place: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// space separated, e.g. `UBAHN BUS`
+    pub products: String,
+}
+
+impl From<&Station> for StationRecord {
+    fn from(station: &Station) -> Self {
+        StationRecord {
+            id: station.id().clone(),
+            name: station.name(),
+            place: station.place(),
+            latitude: station.latitude(),
+            longitude: station.longitude(),
+            products: join(station.products()),
+        }
+    }
+}
+
+/// A station near a position, output of `mvg nearby`
+#[derive(Serialize)]
+pub struct NearbyRecord {
+    /// distance to the position in meters
+    pub distance: f64,
+    pub id: StationId,
+    pub name: String,
+    pub place: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// space separated, e.g. `UBAHN BUS`
+    pub products: String,
+}
+
+impl NearbyRecord {
+    /// `None` for locations which aren't stations
+    pub fn new(nearby: &NearbyLocation) -> Option<Self> {
+        match nearby.location() {
+            Location::Station(station) => {
+                let station = StationRecord::from(station);
+                Some(NearbyRecord {
+                    distance: nearby.distance().round(),
+                    id: station.id,
+                    name: station.name,
+                    place: station.place,
+                    latitude: station.latitude,
+                    longitude: station.longitude,
+                    products: station.products,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A departure, output of `mvg departures`
+#[derive(Serialize)]
+pub struct DepartureRecord {
+    pub station_id: StationId,
+    /// line label, e.g. `U6`
+    pub line: String,
+    pub product: String,
+    pub destination: String,
+    pub planned_departure: String,
+    /// planned departure plus delay
+    pub departure: String,
+    /// in minutes, empty without live data
+    pub delay: Option<i64>,
+    pub platform: Option<String>,
+    pub live: bool,
+    pub cancelled: bool,
+    /// replacement service
+    pub sev: bool,
+    /// minutes until one has to leave to catch the departure, negative if
+    /// it's too late, empty without walking time
+    pub leave_in: Option<i64>,
+}
+
+impl DepartureRecord {
+    pub fn new(station_id: &StationId, departure: &Departure, leave_in: Option<i64>) -> Self {
+        DepartureRecord {
+            station_id: station_id.clone(),
+            line: departure.label(),
+            product: departure.product().to_string(),
+            destination: departure.destination(),
+            planned_departure: departure.departure_time().to_rfc3339(),
+            departure: departure.realtime_departure_time().to_rfc3339(),
+            delay: departure.delay().map(|d| d.num_minutes()),
+            platform: departure.platform().map(|p| p.to_string()),
+            live: departure.live(),
+            cancelled: departure.cancelled(),
+            sev: departure.sev(),
+            leave_in,
+        }
+    }
+}
+
+/// One leg of a connection, output of `mvg connections`
+///
+/// Legs of the same connection share the `connection` number.
+#[derive(Serialize)]
+pub struct ConnectionLegRecord {
+    /// number of the connection, starting at 0
+    pub connection: usize,
+    pub connection_departure: String,
+    pub connection_arrival: String,
+    /// number of the leg within the connection, starting at 0
+    pub leg: usize,
+    /// `TRANSPORTATION` or `FOOTWAY`
+    pub mode: String,
+    /// line label, empty for footways
+    pub line: Option<String>,
+    /// product, empty for footways
+    pub product: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub departure: String,
+    pub arrival: String,
+}
+
+impl ConnectionLegRecord {
+    pub fn legs(number: usize, connection: &Connection) -> Vec<Self> {
+        connection
+            .connection_parts()
+            .iter()
+            .enumerate()
+            .map(|(leg, part)| {
+                let (mode, line, product, from, to, departure, arrival) = match part {
+                    ConnectionPart::Transportation(tp) => (
+                        "TRANSPORTATION",
+                        Some(tp.label().clone()),
+                        Some(tp.product().to_string()),
+                        tp.from(),
+                        tp.to(),
+                        tp.departure_time(),
+                        tp.arrival_time(),
+                    ),
+                    ConnectionPart::Footway(fw) => (
+                        "FOOTWAY",
+                        None,
+                        None,
+                        fw.from(),
+                        fw.to(),
+                        fw.departure_time(),
+                        fw.arrival_time(),
+                    ),
+                };
+                ConnectionLegRecord {
+                    connection: number,
+                    connection_departure: connection.departure_time().to_rfc3339(),
+                    connection_arrival: connection.arrival_time().to_rfc3339(),
+                    leg,
+                    mode: mode.to_string(),
+                    line,
+                    product,
+                    from: location_name(from),
+                    to: location_name(to),
+                    departure: departure.to_rfc3339(),
+                    arrival: arrival.to_rfc3339(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A service interruption, output of `mvg interruptions`
+#[derive(Serialize)]
+pub struct InterruptionRecord {
+    pub id: String,
+    pub title: String,
+    /// affected line labels, space separated
+    pub lines: String,
+    pub valid_from: Option<String>,
+    pub valid_until: Option<String>,
+    /// validity as text provided by the MVG
+    pub validity: String,
+    /// description without markup
+    pub text: String,
+}
+
+impl From<&Interruption> for InterruptionRecord {
+    fn from(interruption: &Interruption) -> Self {
+        InterruptionRecord {
+            id: interruption.id().to_string(),
+            title: interruption.title().to_string(),
+            lines: interruption
+                .affected_lines()
+                .iter()
+                .map(|l| l.label())
+                .collect::<Vec<_>>()
+                .join(" "),
+            valid_from: interruption.valid_from().map(|t| t.to_rfc3339()),
+            valid_until: interruption.valid_until().map(|t| t.to_rfc3339()),
+            validity: interruption.validity_text().to_string(),
+            text: interruption.plain_text(),
+        }
+    }
+}
+
+/// A line serving a station, output of `mvg lines`
+#[derive(Serialize)]
+pub struct LineRecord {
+    pub station_id: StationId,
+    /// group of the line as named by the API, e.g. `ubahn` or `nachtbus`
+    pub group: String,
+    /// line label, e.g. `U6`
+    pub line: String,
+}
+
+impl LineRecord {
+    /// one record per line of `station`
+    pub fn of_station(station: &Station, lines: &Lines) -> Vec<Self> {
+        lines
+            .groups()
+            .into_iter()
+            .flat_map(|(group, labels)| {
+                labels.iter().map(move |label| LineRecord {
+                    station_id: station.id().clone(),
+                    group: group.key().to_string(),
+                    line: label.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A favorite station, output of `mvg fav list`
+#[derive(Serialize)]
+pub struct FavoriteRecord {
+    pub name: String,
+    /// station id or name as configured
+    pub station: String,
+}
+
+impl FavoriteRecord {
+    pub fn new(name: &str, station: &str) -> Self {
+        FavoriteRecord {
+            name: name.to_string(),
+            station: station.to_string(),
+        }
+    }
+}
+
+fn join(products: &[Product]) -> String {
+    products
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Readable name of any kind of location
+pub fn location_name(location: &Location) -> String {
+    match location {
+        Location::Station(s) => s.name(),
+        Location::Address(a) => a.street(),
+        Location::Location(_) => {
+            let (lat, lon) = location.coordinates();
+            format!("{:.5}, {:.5}", lat, lon)
+        }
+    }
+}
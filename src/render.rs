@@ -0,0 +1,132 @@
+use css_color_parser::Color as CssColor;
+use termion::{color, style};
+
+use crate::conf::ColorOption;
+
+/// Ends bold text. termion's `NoBold` is `CSI 21m`, which most terminals
+/// take for double underline.
+const NORMAL_INTENSITY: &str = "\x1b[22m";
+
+/// Applies colors and text styles as allowed by a [`ColorOption`].
+///
+/// With [`ColorOption::No`] every method returns the plain text.
+pub struct Painter {
+    option: ColorOption,
+}
+
+impl Painter {
+    pub fn new(option: ColorOption) -> Self {
+        Painter { option }
+    }
+
+    /// Painter for output to stdout.
+    ///
+    /// Colors are disabled if `NO_COLOR` is set or stdout is no terminal.
+    pub fn for_stdout(configured: ColorOption) -> Self {
+        Self::for_stream(configured, termion::is_tty(&std::io::stdout()), env)
+    }
+
+    /// Painter for output to stderr, see [`Painter::for_stdout`].
+    pub fn for_stderr(configured: ColorOption) -> Self {
+        Self::for_stream(configured, termion::is_tty(&std::io::stderr()), env)
+    }
+
+    /// [`Painter::for_stdout`] with the terminal check and the environment
+    /// passed in
+    pub fn for_stream<E>(configured: ColorOption, is_tty: bool, env: E) -> Self
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let no_color = env("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color || !is_tty {
+            Painter::new(ColorOption::No)
+        } else {
+            Painter::new(configured)
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.option != ColorOption::No
+    }
+
+    /// A line label on its background color with a readable foreground
+    pub fn label(&self, label: &str, background: &CssColor) -> String {
+        let foreground = if is_light(background) {
+            (0, 0, 0)
+        } else {
+            (255, 255, 255)
+        };
+        match self.option {
+            ColorOption::TrueColor => format!(
+                "{}{}{}{}",
+                color::Bg(color::Rgb(background.r, background.g, background.b)),
+                color::Fg(color::Rgb(foreground.0, foreground.1, foreground.2)),
+                label,
+                style::Reset
+            ),
+            ColorOption::Ansi => format!(
+                "{}{}{}{}",
+                color::Bg(ansi(background.r, background.g, background.b)),
+                color::Fg(ansi(foreground.0, foreground.1, foreground.2)),
+                label,
+                style::Reset
+            ),
+            ColorOption::No => label.to_string(),
+        }
+    }
+
+    pub fn bold(&self, text: &str) -> String {
+        self.styled(text, style::Bold, NORMAL_INTENSITY)
+    }
+
+    pub fn inverted(&self, text: &str) -> String {
+        self.styled(text, style::Invert, style::NoInvert)
+    }
+
+    pub fn crossed_out(&self, text: &str) -> String {
+        self.styled(text, style::CrossedOut, style::NoCrossedOut)
+    }
+
+    pub fn faint(&self, text: &str) -> String {
+        self.styled(text, style::Faint, style::NoFaint)
+    }
+
+    pub fn red(&self, text: &str) -> String {
+        self.styled(text, color::Fg(color::Red), color::Fg(color::Reset))
+    }
+
+    fn styled<S: std::fmt::Display, E: std::fmt::Display>(&self, text: &str, start: S, end: E) -> String {
+        if self.enabled() {
+            format!("{}{}{}", start, text, end)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Value of an environment variable, also if it isn't valid unicode
+fn env(var: &str) -> Option<String> {
+    std::env::var_os(var).map(|v| v.to_string_lossy().into_owned())
+}
+
+/// Nearest color of the 6x6x6 color cube of 256 color terminals
+pub fn ansi(r: u8, g: u8, b: u8) -> color::AnsiValue {
+    let adjust = |col| ((col as u16 * 5 + 127) / 255) as u8;
+    color::AnsiValue::rgb(adjust(r), adjust(g), adjust(b))
+}
+
+/// Whether dark text is better readable than white text on `color`,
+/// based on the relative luminance defined by WCAG 2.0.
+pub fn is_light(color: &CssColor) -> bool {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let luminance =
+        0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b);
+    luminance > 0.179
+}
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use mvg_lib::data::connection::ConnectionList;
+use mvg_lib::data::departure::DepartureInfo;
+use mvg_lib::data::interruption::InterruptionList;
+use mvg_lib::data::line::{LineGroup, Lines};
+use mvg_lib::data::location::{Location, Locations, NearbyLocation, Station};
+use mvg_lib::data::station_id::StationId;
+use mvg_lib::provider::Provider;
+use mvg_lib::time_format::DisplayZone;
+use css_color_parser::Color as CssColor;
+use clap::Clap;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::board::Board;
+use crate::cache::locate_index;
+use crate::conf::{locate, parse_config, ColorOption, Config, ConfigKey};
+use crate::output::{
+    ConnectionLegRecord, DepartureRecord, FavoriteRecord, InterruptionRecord, LineRecord,
+    NearbyRecord, StationRecord,
+};
+use crate::render::{ansi, is_light, Painter};
+use crate::{Opts, TimeArg};
+use crate::yaml_edit::{remove_entry, set_entry, set_value, string};
+
+const CONFIG: &str = "\
+# colors of my terminal
+color_option: TrueColor
+
+favorites:
+  # where I live
+  home: \"de:09162:2\"
+  work: Garching
+default_station: home
+";
+
+#[test]
+fn test_set_entry_keeps_comments_and_order() {
+    let text = set_entry(CONFIG, "favorites", "gym", &string("de:09162:6"));
+    assert_eq!(
+        text,
+        "\
+# colors of my terminal
+color_option: TrueColor
+
+favorites:
+  # where I live
+  home: \"de:09162:2\"
+  work: Garching
+  gym: \"de:09162:6\"
+default_station: home
+"
+    );
+    let conf: Config = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(conf.favorites.len(), 3);
+    assert_eq!(conf.station("gym"), "de:09162:6");
+}
+
+#[test]
+fn test_set_entry_replaces_existing() {
+    let text = set_entry(CONFIG, "favorites", "work", &string("de:09184:460"));
+    assert!(text.contains("  work: \"de:09184:460\"\n"));
+    assert!(!text.contains("Garching"));
+    assert!(text.contains("# where I live"));
+}
+
+#[test]
+fn test_set_entry_adds_section() {
+    let text = set_entry("color_option: Ansi\n", "favorites", "home", &string("de:09162:2"));
+    assert_eq!(text, "color_option: Ansi\n\nfavorites:\n  home: \"de:09162:2\"\n");
+
+    let text = set_entry("favorites: {}\n", "favorites", "home", &string("de:09162:2"));
+    assert_eq!(text, "favorites:\n  home: \"de:09162:2\"\n");
+
+    let text = set_entry("", "favorites", "my place", &string("Marienplatz"));
+    let conf: Config = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(conf.station("my place"), "Marienplatz");
+}
+
+#[test]
+fn test_set_entry_inline_section() {
+    let inline = "favorites: {home: \"de:09162:2\", work: Garching} # mine\nwalk_minutes: {home: 5}\n";
+    let text = set_entry(inline, "favorites", "gym", &string("de:09162:6"));
+    assert_eq!(
+        text,
+        "favorites:\n  home: \"de:09162:2\"\n  work: Garching\n  gym: \"de:09162:6\"\nwalk_minutes: {home: 5}\n"
+    );
+
+    let text = set_entry(inline, "walk_minutes", "work", "12");
+    let conf: Config = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(conf.walk_minutes(&["home"]), Some(5));
+    assert_eq!(conf.walk_minutes(&["work"]), Some(12));
+    assert_eq!(conf.station("work"), "Garching");
+
+    let text = remove_entry(inline, "favorites", "home").unwrap();
+    assert!(text.starts_with("favorites:\n  work: Garching\nwalk_minutes:"));
+    assert_eq!(remove_entry(inline, "favorites", "gym"), None);
+}
+
+#[test]
+fn test_remove_entry() {
+    let text = remove_entry(CONFIG, "favorites", "home").unwrap();
+    assert!(!text.contains("home: "));
+    assert!(text.contains("  work: Garching\n"));
+    assert!(text.contains("default_station: home\n"));
+
+    let text = remove_entry(&text, "favorites", "work").unwrap();
+    assert!(text.contains("\nfavorites: {}\n"));
+    let conf: Config = serde_yaml::from_str(&text).unwrap();
+    assert!(conf.favorites.is_empty());
+
+    assert!(remove_entry(CONFIG, "favorites", "gym").is_none());
+    assert!(remove_entry("color_option: No\n", "favorites", "home").is_none());
+}
+
+#[test]
+fn test_station_falls_back_to_input() {
+    let conf: Config = serde_yaml::from_str(CONFIG).unwrap();
+    assert_eq!(conf.station("home"), "de:09162:2");
+    assert_eq!(conf.station("Marienplatz"), "Marienplatz");
+}
+
+#[test]
+fn test_set_value() {
+    let text = set_value(CONFIG, "color_option", &string("Ansi"));
+    assert!(text.starts_with("# colors of my terminal\ncolor_option: Ansi\n"));
+
+    let text = set_value(CONFIG, "unknown", &string("true"));
+    assert!(text.ends_with("default_station: home\nunknown: \"true\"\n"));
+}
+
+#[test]
+fn test_locate_config() {
+    let env = |var: &str| match var {
+        "HOME" => Some(String::from("/home/user")),
+        _ => None,
+    };
+    let xdg = PathBuf::from("/home/user/.config/mvg/config.yaml");
+    let legacy = PathBuf::from("/home/user/.mvg.conf");
+
+    assert_eq!(locate(None, env, |_| false), xdg);
+    assert_eq!(locate(None, env, |p| p == legacy), legacy);
+    assert_eq!(locate(None, env, |_| true), xdg);
+    assert_eq!(locate(Some("mvg.yaml"), env, |_| true), PathBuf::from("mvg.yaml"));
+
+    let env = |var: &str| match var {
+        "MVG_CONFIG" => Some(String::from("/etc/mvg.yaml")),
+        "XDG_CONFIG_HOME" => Some(String::from("/xdg")),
+        _ => None,
+    };
+    assert_eq!(locate(None, env, |_| false), PathBuf::from("/etc/mvg.yaml"));
+    assert_eq!(locate(Some("mvg.yaml"), env, |_| false), PathBuf::from("mvg.yaml"));
+
+    let env = |var: &str| match var {
+        "XDG_CONFIG_HOME" => Some(String::from("/xdg")),
+        _ => None,
+    };
+    assert_eq!(locate(None, env, |_| false), PathBuf::from("/xdg/mvg/config.yaml"));
+}
+
+#[test]
+fn test_locate_station_index() {
+    let env = |var: &str| match var {
+        "HOME" => Some(String::from("/home/user")),
+        "XDG_CACHE_HOME" => Some(String::from("cache")),
+        _ => None,
+    };
+    assert_eq!(
+        locate_index(Provider::Mvg, env),
+        PathBuf::from("/home/user/.cache/mvg/stations-mvg.json")
+    );
+
+    let env = |var: &str| match var {
+        "XDG_CACHE_HOME" => Some(String::from("/xdg")),
+        _ => None,
+    };
+    assert_eq!(locate_index(Provider::Mvg, env), PathBuf::from("/xdg/mvg/stations-mvg.json"));
+}
+
+#[test]
+fn test_config_error_location() {
+    let path = Path::new("config.yaml");
+    let err = parse_config(path, "color_option: Ansi\ndefault_station: [\n").unwrap_err();
+    assert!(err.to_string().starts_with("config.yaml:3:1: "), "{}", err);
+
+    let err = parse_config(path, "favorites: {}\ncolor_option: Purple\n").unwrap_err();
+    let message = err.to_string();
+    assert!(message.starts_with("config.yaml:2:"), "{}", message);
+    assert!(message.contains("Purple"), "{}", message);
+    assert!(!message.contains(" at line "), "{}", message);
+
+    assert!(parse_config(path, "").is_ok());
+    assert!(parse_config(path, crate::conf::TEMPLATE).is_ok());
+}
+
+#[test]
+fn test_config_keys() {
+    assert_eq!(
+        "color_option".parse(),
+        Ok(ConfigKey::Value(String::from("color_option")))
+    );
+    assert_eq!(
+        "favorites.home".parse(),
+        Ok(ConfigKey::Entry(String::from("favorites"), String::from("home")))
+    );
+    assert!("favorites".parse::<ConfigKey>().is_err());
+    assert!("color_option.x".parse::<ConfigKey>().is_err());
+    assert!("colour_option".parse::<ConfigKey>().is_err());
+
+    let conf: Config = serde_yaml::from_str(CONFIG).unwrap();
+    let get = |key: &str| conf.get(&key.parse().unwrap());
+    assert_eq!(get("favorites.work"), Some(serde_yaml::Value::from("Garching")));
+    assert_eq!(get("default_station"), Some(serde_yaml::Value::from("home")));
+    assert_eq!(get("favorites.gym"), None);
+}
+
+#[test]
+fn test_walk_minutes() {
+    let text = set_entry(CONFIG, "walk_minutes", "home", "5");
+    let conf: Config = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(conf.walk_minutes(&["Marienplatz", "home"]), Some(5));
+    assert_eq!(conf.walk_minutes(&["work"]), None);
+    assert_eq!(
+        "walk_minutes.home".parse(),
+        Ok(ConfigKey::Entry(String::from("walk_minutes"), String::from("home")))
+    );
+}
+
+#[test]
+fn test_time_args_in_munich() {
+    let munich = DisplayZone::default();
+    let winter: TimeArg = "2020-11-09 10:40".parse().unwrap();
+    assert_eq!(winter.resolve(munich).unwrap().to_rfc3339(), "2020-11-09T10:40:00+01:00");
+    let summer: TimeArg = "2020-07-01 10:40".parse().unwrap();
+    assert_eq!(summer.resolve(munich).unwrap().to_rfc3339(), "2020-07-01T10:40:00+02:00");
+
+    let zoned: TimeArg = "2020-11-09T10:40:00+00:00".parse().unwrap();
+    assert_eq!(zoned.resolve(munich).unwrap().to_rfc3339(), "2020-11-09T10:40:00+00:00");
+
+    // the clocks skip from 2:00 to 3:00
+    let skipped: TimeArg = "2021-03-28 02:30".parse().unwrap();
+    assert!(skipped.resolve(munich).is_err());
+    assert!("half past ten".parse::<TimeArg>().is_err());
+}
+
+/// Header line of `records` written as CSV, pins field names and order
+fn csv_header<T: Serialize>(records: &[T]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.serialize(record).unwrap();
+    }
+    let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    csv.lines().next().unwrap_or_default().to_string()
+}
+
+fn to_json<T: Serialize>(record: &T) -> Value {
+    serde_json::to_value(record).unwrap()
+}
+
+fn marienplatz() -> Station {
+    let locations: Locations = serde_json::from_str(include_str!(
+        "../mvg_lib/fixtures/api_fahrinfo_location_queryWeb_q_Marienplatz_7fd93651.json"
+    ))
+    .unwrap();
+    locations
+        .locations
+        .into_iter()
+        .find_map(|l| match l {
+            Location::Station(s) => Some(s),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_station_records() {
+    let station = marienplatz();
+    let record = StationRecord::from(&station);
+    assert_eq!(
+        csv_header(&[&record]),
+        "id,name,place,latitude,longitude,products"
+    );
+    let json = to_json(&record);
+    assert_eq!(json["id"], "de:09162:2");
+    assert_eq!(json["name"], "Marienplatz");
+
+    let nearby = NearbyLocation::new(Location::Station(station), 48.1372, 11.5755);
+    let record = NearbyRecord::new(&nearby).unwrap();
+    assert_eq!(
+        csv_header(&[&record]),
+        "distance,id,name,place,latitude,longitude,products"
+    );
+}
+
+#[test]
+fn test_departure_records() {
+    let info: DepartureInfo = serde_json::from_str(include_str!(
+        "../mvg_lib/fixtures/api_fahrinfo_departure_de_09162_2_footway_0_38ae4402.json"
+    ))
+    .unwrap();
+    let records: Vec<_> = info
+        .departures
+        .iter()
+        .map(|d| DepartureRecord::new(marienplatz().id(), d, None))
+        .collect();
+    assert_eq!(
+        csv_header(&records),
+        "station_id,line,product,destination,planned_departure,departure,delay,\
+         platform,live,cancelled,sev,leave_in"
+    );
+    let u6 = to_json(&records[0]);
+    assert_eq!(u6["line"], "U6");
+    assert_eq!(u6["product"], "UBAHN");
+    assert_eq!(u6["planned_departure"], "2020-11-09T11:42:00+01:00");
+    assert_eq!(u6["departure"], "2020-11-09T11:44:00+01:00");
+    assert_eq!(u6["delay"], 2);
+    assert_eq!(u6["platform"], "2");
+    assert_eq!(u6["leave_in"], Value::Null);
+    // optional fields stay in the output as null
+    let bus = to_json(&records[2]);
+    assert_eq!(bus["platform"], Value::Null);
+    assert_eq!(bus["cancelled"], true);
+
+    let record = DepartureRecord::new(marienplatz().id(), &info.departures[0], Some(-1));
+    assert_eq!(to_json(&record)["leave_in"], -1);
+}
+
+#[test]
+fn test_connection_leg_records() {
+    let list: ConnectionList = serde_json::from_str(include_str!(
+        "../mvg_lib/fixtures/api_fahrinfo_routing_fromStation_de_09162_2_toStation_de_09184_460_88b979e2.json"
+    ))
+    .unwrap();
+    let legs = ConnectionLegRecord::legs(0, &list.connection_list[0]);
+    assert_eq!(
+        csv_header(&legs),
+        "connection,connection_departure,connection_arrival,leg,mode,line,product,\
+         from,to,departure,arrival"
+    );
+    let ride = to_json(&legs[0]);
+    assert_eq!(ride["mode"], "TRANSPORTATION");
+    assert_eq!(ride["line"], "U6");
+    assert_eq!(ride["product"], "UBAHN");
+    let walk = to_json(&legs[1]);
+    assert_eq!(walk["mode"], "FOOTWAY");
+    assert_eq!(walk["line"], Value::Null);
+    assert_eq!(walk["product"], Value::Null);
+}
+
+#[test]
+fn test_interruption_records() {
+    let list: InterruptionList = serde_json::from_str(include_str!(
+        "../mvg_lib/fixtures/rest_betriebsaenderungen_api_interruptions_f5da1075.json"
+    ))
+    .unwrap();
+    let records: Vec<_> = list.interruption.iter().map(InterruptionRecord::from).collect();
+    assert_eq!(
+        csv_header(&records),
+        "id,title,lines,valid_from,valid_until,validity,text"
+    );
+    let record = to_json(&records[0]);
+    assert_eq!(record["id"], "34561");
+    assert_eq!(record["lines"], "U3 U6");
+    assert_eq!(record["valid_from"], "2020-11-09T11:40:00+01:00");
+    assert_eq!(record["validity"], "09.11.2020 bis 13.11.2020");
+}
+
+#[test]
+fn test_line_and_favorite_records() {
+    let mut lines = Lines::new();
+    lines.add(LineGroup::UBahn, "U6");
+    lines.add(LineGroup::NightBus, "N40");
+    let records = LineRecord::of_station(&marienplatz(), &lines);
+    assert_eq!(csv_header(&records), "station_id,group,line");
+    assert_eq!(
+        records.iter().map(to_json).collect::<Vec<_>>(),
+        vec![
+            json!({"station_id": "de:09162:2", "group": "ubahn", "line": "U6"}),
+            json!({"station_id": "de:09162:2", "group": "nachtbus", "line": "N40"}),
+        ]
+    );
+
+    let record = FavoriteRecord::new("home", "de:09162:2");
+    assert_eq!(csv_header(&[&record]), "name,station");
+    assert_eq!(to_json(&record), json!({"name": "home", "station": "de:09162:2"}));
+}
+
+#[test]
+fn test_painter_for_stream() {
+    let plain = |_: &str| None;
+    let no_color = |var: &str| Some(String::from("1")).filter(|_| var == "NO_COLOR");
+    let empty_no_color = |var: &str| Some(String::new()).filter(|_| var == "NO_COLOR");
+
+    assert!(Painter::for_stream(ColorOption::Ansi, true, plain).enabled());
+    assert!(!Painter::for_stream(ColorOption::Ansi, false, plain).enabled());
+    assert!(!Painter::for_stream(ColorOption::TrueColor, true, no_color).enabled());
+    // an empty NO_COLOR doesn't count
+    assert!(Painter::for_stream(ColorOption::TrueColor, true, empty_no_color).enabled());
+    assert!(!Painter::for_stream(ColorOption::No, true, plain).enabled());
+}
+
+#[test]
+fn test_painter_styles() {
+    let blue: CssColor = "#0065ae".parse().unwrap();
+    assert_eq!(
+        Painter::new(ColorOption::TrueColor).label("U6", &blue),
+        "\x1b[48;2;0;101;174m\x1b[38;2;255;255;255mU6\x1b[m"
+    );
+    assert_eq!(
+        Painter::new(ColorOption::Ansi).label("U6", &blue),
+        "\x1b[48;5;31m\x1b[38;5;231mU6\x1b[m"
+    );
+    assert_eq!(Painter::new(ColorOption::No).label("U6", &blue), "U6");
+    // only the crossing out ends, not the surrounding styles
+    assert_eq!(Painter::new(ColorOption::Ansi).crossed_out("12:00"), "\x1b[9m12:00\x1b[29m");
+    // normal intensity, 21 would start a double underline
+    assert_eq!(Painter::new(ColorOption::Ansi).bold("Connection 1:"), "\x1b[1mConnection 1:\x1b[22m");
+    assert_eq!(Painter::new(ColorOption::No).bold("Connection 1:"), "Connection 1:");
+}
+
+#[test]
+fn test_colors() {
+    assert_eq!(ansi(0, 0, 0).0, 16);
+    assert_eq!(ansi(255, 255, 255).0, 231);
+    // 0, 101, 174 is 0, 2, 3 in the color cube
+    assert_eq!(ansi(0, 101, 174).0, 16 + 2 * 6 + 3);
+
+    let color = |hex: &str| hex.parse::<CssColor>().unwrap();
+    assert!(is_light(&color("#ffffff")));
+    assert!(is_light(&color("#ffcc00")));
+    assert!(!is_light(&color("#000000")));
+    assert!(!is_light(&color("#0065ae")));
+    assert!(!is_light(&color("#e30613")));
+}
+
+#[test]
+fn test_board_leave_in() {
+    let info: DepartureInfo = serde_json::from_str(include_str!(
+        "../mvg_lib/fixtures/api_fahrinfo_departure_de_09162_2_footway_0_38ae4402.json"
+    ))
+    .unwrap();
+    // the U6 leaves at 10:44 UTC, 5 minutes of walking make it 10:39
+    let u6 = &info.departures[0];
+    let mut walk_minutes = HashMap::new();
+    let station = marienplatz();
+    let id = station.id().clone();
+    walk_minutes.insert(id.clone(), 5);
+    let board = Board::new(vec![station], walk_minutes);
+    let elsewhere: StationId = "de:09162:1".parse().unwrap();
+    let at = |time: &str| format!("2020-11-09T{}Z", time).parse::<DateTime<Utc>>().unwrap();
+
+    assert_eq!(board.leave_in(&id, u6, at("10:30:00")), Some(9));
+    // rounded down
+    assert_eq!(board.leave_in(&id, u6, at("10:37:30")), Some(1));
+    assert_eq!(board.leave_in(&id, u6, at("10:38:59")), Some(0));
+    assert_eq!(board.leave_in(&id, u6, at("10:39:00")), Some(0));
+    assert_eq!(board.leave_in(&id, u6, at("10:39:30")), Some(-1));
+    assert_eq!(board.leave_in(&id, u6, at("10:42:00")), Some(-3));
+
+    assert!(board.reachable(&id, u6, at("10:39:00")));
+    assert!(!board.reachable(&id, u6, at("10:39:30")));
+    assert_eq!(board.leave_note(&id, u6, at("10:37:30")).unwrap(), "leave in 1 min");
+    assert_eq!(board.leave_note(&id, u6, at("10:39:00")).unwrap(), "leave now");
+    assert_eq!(board.leave_note(&id, u6, at("10:42:00")).unwrap(), "too late");
+
+    // no walking time, every departure can be reached
+    assert_eq!(board.leave_in(&elsewhere, u6, at("10:50:00")), None);
+    assert!(board.reachable(&elsewhere, u6, at("10:50:00")));
+    assert_eq!(board.leave_note(&elsewhere, u6, at("10:50:00")), None);
+}
+
+#[test]
+fn test_sync_takes_no_search_term() {
+    assert!(Opts::try_parse_from(["mvg", "stations", "--sync"]).is_ok());
+    assert!(Opts::try_parse_from(["mvg", "stations", "Marienplatz"]).is_ok());
+    assert!(Opts::try_parse_from(["mvg", "stations", "--sync", "Marienplatz"]).is_err());
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use chrono::Duration as ChronoDuration;
+use mvg_lib::data::departure::Departure;
+use mvg_lib::data::station_id::StationId;
+use mvg_lib::provider::TransitProvider;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use termion::{clear, cursor};
+
+use crate::board::Board;
+use crate::{Departures, PAINTER, TIME};
+
+/// Time between two redraws of the board
+const TICK: Duration = Duration::from_millis(200);
+/// Lines used by the header above the departures
+const HEADER_LINES: u16 = 3;
+
+/// Show a full-screen departure board which refreshes every `opts.interval`
+/// seconds until the user presses q, Esc or Ctrl-C.
+pub async fn watch_departures(board: &Board, opts: &Departures, provider: &dyn TransitProvider) -> io::Result<()> {
+    let stdout = io::stdout().into_raw_mode()?;
+    let mut screen = cursor::HideCursor::from(AlternateScreen::from(stdout));
+    let mut keys = termion::async_stdin().keys();
+
+    let interval = Duration::from_secs(opts.interval.max(1));
+    let filter = opts.filter();
+    let ids = board.station_ids();
+    let mut departures: Vec<(StationId, Departure)> = Vec::new();
+    let mut delays: HashMap<String, Option<ChronoDuration>> = HashMap::new();
+    let mut changed: Vec<String> = Vec::new();
+    let mut error: Option<String> = None;
+    let mut last_fetch: Option<Instant> = None;
+
+    loop {
+        if last_fetch.is_none_or(|t| t.elapsed() >= interval) {
+            let fetched = provider.departures_multi(&ids).await;
+            // keep the departures of stations which failed this time
+            let failed: Vec<&StationId> = fetched.errors().iter().map(|(id, _)| id).collect();
+            departures.retain(|(id, _)| failed.contains(&id));
+            departures.extend(
+                fetched
+                    .departures()
+                    .iter()
+                    .filter(|(_, d)| filter.matches(d))
+                    .cloned(),
+            );
+            departures.sort_by_key(|(_, d)| d.realtime_departure_time());
+            // only departures seen before can have a changed delay
+            changed = departures
+                .iter()
+                .filter(|(_, d)| {
+                    delays
+                        .get(&d.departure_id())
+                        .is_some_and(|delay| *delay != d.delay())
+                })
+                .map(|(_, d)| d.departure_id())
+                .collect();
+            delays = departures
+                .iter()
+                .map(|(_, d)| (d.departure_id(), d.delay()))
+                .collect();
+            error = fetched.errors().iter().next().map(|(id, e)| {
+                format!("{}: {}", board.station_name(id), e)
+            });
+            last_fetch = Some(Instant::now());
+        }
+
+        let next_refresh = last_fetch
+            .map(|t| interval.saturating_sub(t.elapsed()))
+            .unwrap_or_default();
+        draw(&mut screen, board, opts, &departures, &changed, error.as_deref(), next_refresh)?;
+
+        for key in keys.by_ref() {
+            match key? {
+                Key::Char('q') | Key::Ctrl('c') | Key::Esc => return Ok(()),
+                Key::Char('r') => last_fetch = None,
+                _ => {}
+            }
+        }
+        tokio::time::delay_for(TICK).await;
+    }
+}
+
+fn draw<W: Write>(
+    screen: &mut W,
+    board: &Board,
+    opts: &Departures,
+    departures: &[(StationId, Departure)],
+    changed: &[String],
+    error: Option<&str>,
+    next_refresh: Duration,
+) -> io::Result<()> {
+    let now = TIME.now();
+    let (_, height) = termion::terminal_size().unwrap_or((80, 24));
+    let rows = height.saturating_sub(HEADER_LINES) as usize;
+    let limit = opts.limit.unwrap_or(rows).min(rows);
+
+    write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+    write!(
+        screen,
+        "{}\t{}\r\n",
+        PAINTER.bold(&board.title()),
+        TIME.absolute_seconds(&now)
+    )?;
+    match error {
+        Some(e) => write!(screen, "Update failed: {}", e)?,
+        None => write!(
+            screen,
+            "Next update in {}s, r to update now, q to quit",
+            next_refresh.as_secs()
+        )?,
+    }
+    write!(screen, "\r\n\r\n")?;
+
+    // departures which are gone already are dropped until the next update
+    let upcoming = departures
+        .iter()
+        .filter(|(_, d)| d.realtime_departure_time() >= now - ChronoDuration::seconds(30))
+        .filter(|(id, d)| !opts.hide_unreachable || board.reachable(id, d, now));
+    for (id, dep) in upcoming.take(limit) {
+        let countdown = TIME.relative(&dep.realtime_departure_time());
+        let highlight = changed.contains(&dep.departure_id());
+        write!(
+            screen,
+            "{}\t{:>10}\r\n",
+            board.line(id, dep, highlight, now),
+            countdown
+        )?;
+    }
+    screen.flush()
+}
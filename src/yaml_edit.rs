@@ -0,0 +1,173 @@
+//! Minimal line based editing of the config file.
+//!
+//! serde_yaml drops comments and reorders nothing but rewrites everything,
+//! so entries are edited in the text directly. Sections are written in the
+//! simple block style used by the config file, flow mappings like
+//! `favorites: {}` are turned into blocks when they are edited:
+//!
+//! ```yaml
+//! favorites:
+//!   home: "de:09162:2"
+//! ```
+
+/// Set the top level `key` to `value`, a YAML scalar like the ones made by
+/// [`string`].
+///
+/// An existing key keeps its position, a new one is appended to the file.
+pub fn set_value(text: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let line = format!("{}: {}", string(key), value);
+    match find_section(&lines, key) {
+        Some(i) => lines[i] = line,
+        None => lines.push(line),
+    }
+    join(lines)
+}
+
+/// Set `key` to `value` in the top level mapping `section`, `value` is a YAML
+/// scalar like the ones made by [`string`].
+///
+/// Existing entries keep their position, new entries are added at the end of
+/// the section. Missing sections are appended to the file.
+pub fn set_entry(text: &str, section: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let entry = |indent: &str| format!("{}{}: {}", indent, string(key), value);
+
+    let start = match find_section(&lines, section) {
+        Some(start) => start,
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("{}:", section));
+            lines.push(entry("  "));
+            return join(lines);
+        }
+    };
+    to_block(&mut lines, start, section);
+
+    let (entries, end) = section_entries(&lines, start);
+    if let Some(&(line, ref indent)) = entries.iter().find(|(line, _)| entry_key(&lines[*line]) == key) {
+        lines[line] = entry(indent);
+        return join(lines);
+    }
+    let indent = entries
+        .first()
+        .map(|(_, indent)| indent.clone())
+        .unwrap_or_else(|| String::from("  "));
+    let insert_at = entries.last().map(|(line, _)| line + 1).unwrap_or(end);
+    lines.insert(insert_at, entry(&indent));
+    join(lines)
+}
+
+/// Remove `key` from the top level mapping `section`.
+///
+/// Returns `None` if there is no such entry.
+pub fn remove_entry(text: &str, section: &str, key: &str) -> Option<String> {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let start = find_section(&lines, section)?;
+    to_block(&mut lines, start, section);
+    let (entries, _) = section_entries(&lines, start);
+    let (line, _) = entries
+        .into_iter()
+        .find(|(line, _)| entry_key(&lines[*line]) == key)?;
+    lines.remove(line);
+    // a section without entries would be null instead of an empty mapping
+    if section_entries(&lines, start).0.is_empty() {
+        lines[start] = format!("{}: {{}}", section);
+    }
+    Some(join(lines))
+}
+
+/// Turn `section: {home: "de:09162:2"}` or `section: ~` starting at `start`
+/// into a block, the entries of a flow mapping become lines of the block.
+fn to_block(lines: &mut Vec<String>, start: usize, section: &str) {
+    let inline_value = lines[start].split_once(':').map_or("", |(_, v)| v.trim());
+    if inline_value.is_empty() || inline_value.starts_with('#') {
+        return;
+    }
+    let entries: Vec<String> = match serde_yaml::from_str(inline_value) {
+        Ok(serde_yaml::Value::Mapping(mapping)) => mapping
+            .iter()
+            .map(|(key, value)| format!("  {}: {}", flow(key), flow(value)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    lines[start] = format!("{}:", section);
+    lines.splice(start + 1..start + 1, entries);
+}
+
+/// YAML for a value of a flow mapping that fits on one line
+fn flow(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(s) => string(s),
+        // JSON is valid YAML and never spans several lines
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Line of `section:` at the top level
+fn find_section(lines: &[String], section: &str) -> Option<usize> {
+    lines.iter().position(|l| {
+        !l.starts_with(|c: char| c.is_whitespace() || c == '#') && entry_key(l) == section
+    })
+}
+
+/// Lines and indentation of the entries of the section starting at `start`,
+/// and the line after the section
+fn section_entries(lines: &[String], start: usize) -> (Vec<(usize, String)>, usize) {
+    let mut entries = Vec::new();
+    let mut end = start + 1;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            break;
+        }
+        let indent = line[..line.len() - trimmed.len()].to_string();
+        entries.push((i, indent));
+        end = i + 1;
+    }
+    (entries, end)
+}
+
+/// Unquoted key of a `key: value` line
+fn entry_key(line: &str) -> String {
+    let line = line.trim();
+    let key = if let Some(rest) = line.strip_prefix('"') {
+        rest.split('"').next().unwrap_or("")
+    } else if let Some(rest) = line.strip_prefix('\'') {
+        rest.split('\'').next().unwrap_or("")
+    } else {
+        line.split(':').next().unwrap_or("")
+    };
+    key.trim().to_string()
+}
+
+/// YAML scalar for the string `s`, plain if possible, quoted otherwise
+pub fn string(s: &str) -> String {
+    let simple = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+    // plain scalars like `true` or `12` wouldn't be strings
+    let plain = simple
+        && matches!(serde_yaml::from_str(s), Ok(serde_yaml::Value::String(ref p)) if p == s);
+    if plain {
+        s.to_string()
+    } else {
+        quote(s)
+    }
+}
+
+/// Double quoted YAML scalar
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn join(lines: Vec<String>) -> String {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}